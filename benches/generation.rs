@@ -0,0 +1,149 @@
+//! Candidate scoring and generation on a synthetic 100k-song library.
+//!
+//! Run with `cargo bench --bench generation`. Compares rebuilding the playlist's
+//! metadata for every candidate (how the generator scored candidates before) with the
+//! incremental scorer, then times a full `generate_playlist` run.
+
+use playlist_generator::models::{Genre, Song};
+use playlist_generator::playlist::incremental::IncrementalQuality;
+use playlist_generator::playlist::scoring::PlaylistScoring;
+use playlist_generator::playlist::{PlaylistConfig, PlaylistGenerator};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const LIBRARY_SIZE: usize = 100_000;
+const PLAYLIST_LENGTH: usize = 40;
+/// Candidates scored at each position; the full rescoring is too slow for all of them
+const CANDIDATES_PER_POSITION: usize = 2_000;
+
+const GENRES: [&str; 12] = [
+    "Jazz",
+    "Bebop",
+    "Soul",
+    "Funk",
+    "Rock",
+    "Indie Rock",
+    "Electronic",
+    "House",
+    "Hip-Hop",
+    "Classical",
+    "Folk",
+    "Blues",
+];
+
+fn synthetic_library(rng: &mut StdRng) -> Vec<Song> {
+    (0..LIBRARY_SIZE)
+        .map(|i| {
+            let artist = rng.gen_range(0..LIBRARY_SIZE / 10);
+            Song {
+                id: format!("song-{i}"),
+                title: format!("Song {i}"),
+                artist: format!("Artist {artist}"),
+                album: format!("Album {}", rng.gen_range(0..LIBRARY_SIZE / 8)),
+                genre: Some(GENRES[rng.gen_range(0..GENRES.len())].to_string()),
+                genres: rng.gen_bool(0.3).then(|| {
+                    vec![Genre {
+                        name: GENRES[rng.gen_range(0..GENRES.len())].to_string(),
+                    }]
+                }),
+                bpm: rng.gen_bool(0.9).then(|| rng.gen_range(70..170)),
+                duration: Some(rng.gen_range(120..420)),
+                year: rng.gen_bool(0.95).then(|| rng.gen_range(1955..2025)),
+                track: Some(rng.gen_range(1..15)),
+                play_count: rng.gen_bool(0.8).then(|| rng.gen_range(0..300)),
+                disc_number: None,
+                album_id: None,
+                artist_id: Some(format!("artist-{artist}")),
+                played: None,
+                starred: None,
+                bit_rate: None,
+                content_type: None,
+            }
+        })
+        .collect()
+}
+
+/// Score `CANDIDATES_PER_POSITION` candidates at every position of a playlist built
+/// from the start of the library, returning the elapsed time and a checksum
+fn full_rescoring(config: &PlaylistConfig, library: &[Song]) -> (Duration, f32) {
+    let start = Instant::now();
+    let mut checksum = 0.0;
+    for position in 0..PLAYLIST_LENGTH {
+        let playlist = &library[..position];
+        for candidate in &library[PLAYLIST_LENGTH..PLAYLIST_LENGTH + CANDIDATES_PER_POSITION] {
+            let mut test_playlist = playlist.to_vec();
+            test_playlist.push(candidate.clone());
+            let metadata = PlaylistScoring::calculate_metadata(&test_playlist);
+            checksum += PlaylistScoring::calculate_quality_score(&test_playlist, &metadata, config);
+        }
+    }
+    (start.elapsed(), black_box(checksum))
+}
+
+/// The same workload with the incremental scorer, including extracting the features
+fn incremental_scoring(config: &PlaylistConfig, library: &[Song]) -> (Duration, f32) {
+    let start = Instant::now();
+    let mut quality = IncrementalQuality::new(config);
+    let candidates: Vec<_> = library[PLAYLIST_LENGTH..PLAYLIST_LENGTH + CANDIDATES_PER_POSITION]
+        .iter()
+        .map(|song| quality.features(song))
+        .collect();
+    let mut checksum = 0.0;
+    for song in &library[..PLAYLIST_LENGTH] {
+        for candidate in &candidates {
+            checksum += quality.score_with(candidate);
+        }
+        let features = quality.features(song);
+        quality.push(&features);
+    }
+    (start.elapsed(), black_box(checksum))
+}
+
+fn main() {
+    let mut rng = StdRng::seed_from_u64(2024);
+    let library = synthetic_library(&mut rng);
+    let config = PlaylistConfig::default();
+    let evaluations = PLAYLIST_LENGTH * CANDIDATES_PER_POSITION;
+
+    println!(
+        "Scoring {} candidates at each of {} positions ({} evaluations)",
+        CANDIDATES_PER_POSITION, PLAYLIST_LENGTH, evaluations
+    );
+    let (full, full_checksum) = full_rescoring(&config, &library);
+    let (incremental, incremental_checksum) = incremental_scoring(&config, &library);
+    let per_evaluation = |elapsed: Duration| elapsed.as_nanos() as f64 / evaluations as f64;
+    println!(
+        "  full rescoring: {:>10.2?} ({:.0} ns per candidate)",
+        full,
+        per_evaluation(full)
+    );
+    println!(
+        "  incremental:    {:>10.2?} ({:.0} ns per candidate)",
+        incremental,
+        per_evaluation(incremental)
+    );
+    println!(
+        "  speedup:        {:.1}x",
+        full.as_secs_f64() / incremental.as_secs_f64()
+    );
+    assert!(
+        (full_checksum - incremental_checksum).abs() / full_checksum < 1e-3,
+        "incremental scores diverged: {full_checksum} vs {incremental_checksum}"
+    );
+
+    println!(
+        "Generating a {}-song playlist from {} songs",
+        PLAYLIST_LENGTH, LIBRARY_SIZE
+    );
+    let generator = PlaylistGenerator::new(config).with_rng(StdRng::seed_from_u64(2024));
+    let start = Instant::now();
+    let playlist = generator.generate_playlist(library, None, Some(PLAYLIST_LENGTH));
+    println!(
+        "  generate_playlist: {:.2?} ({} songs, quality {:.1}%)",
+        start.elapsed(),
+        playlist.songs.len(),
+        playlist.quality_score * 100.0
+    );
+}
@@ -0,0 +1,87 @@
+use crate::models::OpenSubsonicExtension;
+use std::fmt;
+
+/// What the connected server reported about itself, gathered once per run from
+/// `ping` and `getOpenSubsonicExtensions`
+#[derive(Debug, Clone, Default)]
+pub struct ServerCapabilities {
+    /// REST API version the server speaks, e.g. "1.16.1"
+    pub api_version: String,
+    /// Server implementation, e.g. "navidrome" (OpenSubsonic only)
+    pub server_type: Option<String>,
+    /// Version of the server implementation (OpenSubsonic only)
+    pub server_version: Option<String>,
+    pub open_subsonic: bool,
+    pub extensions: Vec<OpenSubsonicExtension>,
+}
+
+impl ServerCapabilities {
+    /// Whether the server advertises the given OpenSubsonic extension
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|ext| ext.name == name)
+    }
+
+    /// Playlist writes can be sent as a form-encoded POST body
+    pub fn supports_form_post(&self) -> bool {
+        self.has_extension("formPost")
+    }
+
+    /// Requests can authenticate with an `apiKey` instead of username and password
+    pub fn supports_api_key_auth(&self) -> bool {
+        self.has_extension("apiKeyAuthentication")
+    }
+
+    /// Songs carry a `genres` array in addition to the single legacy `genre` field
+    pub fn supports_multiple_genres(&self) -> bool {
+        self.open_subsonic
+    }
+
+    /// Synced and unsynced lyrics are available through `getLyricsBySongId`
+    pub fn supports_lyrics(&self) -> bool {
+        self.has_extension("songLyrics")
+    }
+}
+
+impl fmt::Display for ServerCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+
+        writeln!(
+            f,
+            "Server:        {} {}",
+            self.server_type.as_deref().unwrap_or("unknown"),
+            self.server_version.as_deref().unwrap_or("")
+        )?;
+        writeln!(f, "API version:   {}", self.api_version)?;
+        writeln!(f, "OpenSubsonic:  {}", yes_no(self.open_subsonic))?;
+
+        writeln!(f, "Features:")?;
+        writeln!(
+            f,
+            "  Multiple genres:   {}",
+            yes_no(self.supports_multiple_genres())
+        )?;
+        writeln!(
+            f,
+            "  Form POST:         {}",
+            yes_no(self.supports_form_post())
+        )?;
+        writeln!(
+            f,
+            "  API key auth:      {}",
+            yes_no(self.supports_api_key_auth())
+        )?;
+        writeln!(f, "  Lyrics:            {}", yes_no(self.supports_lyrics()))?;
+
+        if self.extensions.is_empty() {
+            write!(f, "Extensions:    none")
+        } else {
+            write!(f, "Extensions:")?;
+            for ext in &self.extensions {
+                let versions: Vec<String> = ext.versions.iter().map(u32::to_string).collect();
+                write!(f, "\n  {} (v{})", ext.name, versions.join(", v"))?;
+            }
+            Ok(())
+        }
+    }
+}
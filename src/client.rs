@@ -1,34 +1,112 @@
-use crate::config::Config;
+use crate::capabilities::ServerCapabilities;
+use crate::config::{Auth, Config, HttpPolicy, RequestLog};
+use crate::error::SubsonicError;
 use crate::models::{
-    CreatePlaylistResponse, GetPlaylistsResponse, PlaylistInfo, RandomSongsResponse, Song,
+    AlbumInfo, AlbumListPayload, AlbumPayload, CreatePlaylistPayload, EmptyPayload,
+    ExtensionsPayload, OpenSubsonicExtension, PingPayload, PlaylistEntry, PlaylistInfo,
+    PlaylistPayload, PlaylistSongsPayload, PlaylistsPayload, RandomSongsPayload, Song,
+    StarredPayload, SubsonicEnvelope, SubsonicResponse,
 };
-use anyhow::Result;
-use ureq::Agent;
+use crate::source::{LibraryProgress, MusicSource, PlaylistSink};
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use std::cell::{Cell, OnceCell};
+use std::collections::HashSet;
+use std::time::Duration;
+use ureq::{Agent, AgentBuilder};
 use urlencoding::encode;
 
-/// A simple Subsonic API client using MD5 authentication
+/// Maximum page size accepted by getAlbumList2
+const ALBUM_LIST_PAGE_SIZE: u32 = 500;
+
+/// API version and client name sent with every request
+const API_VERSION: &str = "1.16.1";
+const CLIENT_NAME: &str = "playlist-generator";
+
+/// Maximum number of song IDs or indexes per GET request when form POST is unavailable
+const GET_CHUNK_SIZE: usize = 50;
+
+/// Query parameters that carry credentials and must never be logged
+const CREDENTIAL_PARAMS: [&str; 5] = ["u", "t", "s", "p", "apiKey"];
+
+/// Query parameters sent with every request, left out of summary logs
+const PROTOCOL_PARAMS: [&str; 3] = ["v", "c", "f"];
+
+/// Endpoints that change server state, logged even in summary mode
+const WRITE_ENDPOINTS: [&str; 3] = ["createPlaylist", "updatePlaylist", "deletePlaylist"];
+
+/// Upper bound for a single backoff delay between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A simple Subsonic API client
 pub struct SubsonicClient {
     agent: Agent,
     base_url: String,
-    username: String,
-    password: String,
+    auth: Auth,
+    /// Set when the server rejected token auth and accepted the encoded password instead
+    token_auth_rejected: Cell<bool>,
+    http: HttpPolicy,
+    request_log: RequestLog,
+    capabilities: OnceCell<ServerCapabilities>,
+}
+
+/// One updatePlaylist call: indexes to remove from the current contents, then songs to append
+struct PlaylistEdit {
+    remove_indexes: Vec<usize>,
+    add_ids: Vec<String>,
+}
+
+impl PlaylistEdit {
+    /// The playlist contents expected after this edit is applied to `before`
+    fn apply(&self, before: &[String]) -> Vec<String> {
+        let removed: HashSet<usize> = self.remove_indexes.iter().copied().collect();
+        before
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !removed.contains(idx))
+            .map(|(_, id)| id.clone())
+            .chain(self.add_ids.iter().cloned())
+            .collect()
+    }
+}
+
+/// How a request is sent to the server
+#[derive(Debug, Clone, Copy)]
+enum Method {
+    Get,
+    /// Parameters in a form-encoded body (OpenSubsonic `formPost` extension)
+    FormPost,
+}
+
+/// What to do after a retryable failure of a request that is not idempotent
+enum RetryCheck<T> {
+    /// Nothing was applied on the server, so it is safe to send the request again
+    Retry,
+    /// The request turned out to have succeeded despite the error
+    Done(T),
 }
 
 impl SubsonicClient {
     /// Create a new client with configuration from environment
     pub fn new(config: Config) -> Self {
-        let agent = Agent::new();
+        let agent = AgentBuilder::new()
+            .timeout_connect(config.http.connect_timeout)
+            .timeout_read(config.http.read_timeout)
+            .build();
 
         SubsonicClient {
             agent,
             base_url: config.base_url,
-            username: config.username,
-            password: config.password,
+            auth: config.auth,
+            token_auth_rejected: Cell::new(false),
+            http: config.http,
+            request_log: config.request_log,
+            capabilities: OnceCell::new(),
         }
     }
 
     /// Generate authentication parameters using salt + token method
-    fn generate_auth_params(&self) -> (String, String) {
+    fn generate_auth_params(password: &str) -> (String, String) {
         // Generate a random salt (at least 6 characters)
         let salt = format!(
             "{:x}",
@@ -44,52 +122,121 @@ impl SubsonicClient {
             .to_string();
 
         // Calculate token = md5(password + salt)
-        let token = format!("{:x}", md5::compute(format!("{}{}", self.password, salt)));
+        let token = format!("{:x}", md5::compute(format!("{}{}", password, salt)));
 
         (salt, token)
     }
 
-    /// Test the API connection with a simple ping - try both auth methods
-    pub fn ping(&self) -> Result<String> {
-        // First try token-based auth (v1.13.0+)
-        let (salt, token) = self.generate_auth_params();
-
-        let url_token = format!(
-            "{}/rest/ping?u={}&t={}&s={}&v=1.16.1&c=PlaylistGenerator&f=json",
-            self.base_url.trim_end_matches('/'),
-            encode(&self.username),
-            token,
-            salt
-        );
-
-        let response = self
-            .agent
-            .get(&url_token)
-            .call()
-            .map_err(|e| anyhow::anyhow!("Ping failed: {}", e))?;
+    /// The authentication method in effect, taking a rejected token fallback into account
+    fn active_auth(&self) -> Auth {
+        match &self.auth {
+            Auth::Token { username, password } if self.token_auth_rejected.get() => {
+                Auth::EncodedPassword {
+                    username: username.clone(),
+                    password: password.clone(),
+                }
+            }
+            auth => auth.clone(),
+        }
+    }
 
-        let response_text = response.into_string()?;
+    /// Query parameters that authenticate a single request
+    fn auth_params(auth: &Auth) -> Vec<(&'static str, String)> {
+        match auth {
+            Auth::ApiKey(key) => vec![("apiKey", key.clone())],
+            Auth::Token { username, password } => {
+                let (salt, token) = Self::generate_auth_params(password);
+                vec![("u", username.clone()), ("t", token), ("s", salt)]
+            }
+            Auth::EncodedPassword { username, password } => {
+                let hex: String = password.bytes().map(|b| format!("{b:02x}")).collect();
+                vec![("u", username.clone()), ("p", format!("enc:{hex}"))]
+            }
+        }
+    }
 
-        // If token auth failed, try password auth
-        if response_text.contains("\"status\":\"failed\"") {
-            let url_password = format!(
-                "{}/rest/ping?u={}&p={}&v=1.12.0&c=PlaylistGenerator&f=json",
-                self.base_url.trim_end_matches('/'),
-                encode(&self.username),
-                encode(&self.password)
-            );
+    /// Test the API connection with a simple ping.
+    /// The first successful ping also records what the server supports.
+    pub fn ping(&self) -> Result<&ServerCapabilities> {
+        let response_text = self.send_idempotent("ping", &[]).context("Ping failed")?;
+
+        let response = match Self::parse_full_response::<PingPayload>(&response_text) {
+            Ok(response) => response,
+            // If token auth was rejected, try the encoded password and keep using it
+            Err(e) if e.is_auth_failure() && matches!(self.active_auth(), Auth::Token { .. }) => {
+                self.token_auth_rejected.set(true);
+                let response_text2 = self
+                    .send_idempotent("ping", &[])
+                    .context("Password ping failed")?;
+
+                match Self::parse_full_response::<PingPayload>(&response_text2) {
+                    Ok(response) => {
+                        println!(
+                            "Token authentication was rejected ({e}); using the encoded password for this run. Set AUTH_METHOD=password to skip the token attempt."
+                        );
+                        response
+                    }
+                    Err(e2) => {
+                        self.token_auth_rejected.set(false);
+                        return Err(e2.into());
+                    }
+                }
+            }
+            // An API key can only work if the server has the extension; say so plainly
+            // rather than reporting the key itself as wrong
+            Err(e)
+                if e.is_auth_failure()
+                    && matches!(self.active_auth(), Auth::ApiKey(_))
+                    && !self.advertises_api_key_auth() =>
+            {
+                return Err(anyhow::anyhow!(
+                    "API_KEY is set but the server does not advertise the apiKeyAuthentication extension ({e}); set USERNAME and PASSWORD instead"
+                ));
+            }
+            Err(e) => return Err(e.into()),
+        };
 
-            let response2 = self
-                .agent
-                .get(&url_password)
-                .call()
-                .map_err(|e| anyhow::anyhow!("Password ping failed: {}", e))?;
+        Ok(self
+            .capabilities
+            .get_or_init(|| self.negotiate_capabilities(response)))
+    }
 
-            let response_text2 = response2.into_string()?;
+    /// Whether getOpenSubsonicExtensions lists API key authentication. The endpoint needs
+    /// no credentials, so this works even when the key was rejected.
+    fn advertises_api_key_auth(&self) -> bool {
+        self.get_open_subsonic_extensions()
+            .unwrap_or_default()
+            .iter()
+            .any(|ext| ext.name == "apiKeyAuthentication")
+    }
 
-            Ok(response_text2)
+    /// Build the capability set from a ping response. Extensions are only queried on
+    /// OpenSubsonic servers; a failed lookup just means none are used.
+    fn negotiate_capabilities(&self, ping: SubsonicResponse<PingPayload>) -> ServerCapabilities {
+        let extensions = if ping.payload.open_subsonic {
+            self.get_open_subsonic_extensions().unwrap_or_default()
         } else {
-            Ok(response_text)
+            Vec::new()
+        };
+
+        ServerCapabilities {
+            api_version: ping.version,
+            server_type: ping.payload.server_type,
+            server_version: ping.payload.server_version,
+            open_subsonic: ping.payload.open_subsonic,
+            extensions,
+        }
+    }
+
+    /// What the server supports, negotiated on first use. If the server cannot be
+    /// reached, nothing beyond the plain Subsonic API is assumed.
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        if let Some(capabilities) = self.capabilities.get() {
+            return capabilities;
+        }
+        match self.ping() {
+            Ok(capabilities) => capabilities,
+            Err(_) => self.capabilities.get_or_init(ServerCapabilities::default),
         }
     }
 
@@ -150,172 +297,199 @@ impl SubsonicClient {
     
     /// Internal helper to fetch a single batch of random songs
     fn fetch_songs_batch(&self, size: u32) -> Result<Vec<Song>> {
-        let (salt, token) = self.generate_auth_params();
-
-        // Build URL with query parameters
-        let url = format!(
-            "{}/rest/getRandomSongs?u={}&t={}&s={}&v=1.16.1&c=PlaylistGenerator&f=json&size={}",
-            self.base_url.trim_end_matches('/'),
-            self.username,
-            token,
-            salt,
-            size
-        );
+        // Send GET request and parse the response envelope
+        let payload: RandomSongsPayload =
+            self.fetch("getRandomSongs", &[("size", size.to_string())])?;
 
-        // Send GET request
-        let response = self
-            .agent
-            .get(&url)
-            .call()
-            .map_err(|e| anyhow::anyhow!("HTTP request failed: {}", e))?;
+        // Extract songs from response
+        Ok(self.with_genre_support(
+            payload
+                .random_songs
+                .map(|random_songs| random_songs.song)
+                .unwrap_or_default(),
+        ))
+    }
 
-        let response_text = response.into_string()?;
+    /// Only OpenSubsonic servers define the `genres` array. Elsewhere it is dropped, so
+    /// genre filters and reports use the single `genre` field the server actually sets.
+    fn with_genre_support(&self, mut songs: Vec<Song>) -> Vec<Song> {
+        if !self.capabilities().supports_multiple_genres() {
+            for song in &mut songs {
+                song.genres = None;
+            }
+        }
+        songs
+    }
 
-        // Debug: print response to see what we're getting
-        // println!("API Response: {}", response_text);
+    /// Fetch every song in the library by paging through getAlbumList2 and
+    /// loading each album with getAlbum. The callback is invoked after each album.
+    pub fn fetch_all_songs<F>(&self, mut on_progress: F) -> Result<Vec<Song>>
+    where
+        F: FnMut(&LibraryProgress),
+    {
+        let albums = self.fetch_all_albums()?;
 
-        // Parse JSON response
-        let parsed_response: RandomSongsResponse = serde_json::from_str(&response_text)
-            .map_err(|e| anyhow::anyhow!("Failed to parse JSON response: {}", e))?;
+        let mut all_songs = Vec::new();
+        let mut seen_ids = std::collections::HashSet::new();
 
-        // Check if the response is successful
-        if parsed_response.subsonic_response.status != "ok" {
-            return Err(anyhow::anyhow!(
-                "API returned error status: {}",
-                parsed_response.subsonic_response.status
-            ));
-        }
+        for (index, album) in albums.iter().enumerate() {
+            for song in self.fetch_album_songs(&album.id)? {
+                if seen_ids.insert(song.id.clone()) {
+                    all_songs.push(song);
+                }
+            }
 
-        // Extract songs from response
-        match parsed_response.subsonic_response.random_songs {
-            Some(random_songs) => Ok(random_songs.song),
-            None => Ok(vec![]),
+            on_progress(&LibraryProgress {
+                albums_scanned: index + 1,
+                albums_total: albums.len(),
+                songs_found: all_songs.len(),
+            });
         }
-    }
-
-    /// Get all existing playlists
-    pub fn get_playlists(&self) -> Result<Vec<PlaylistInfo>> {
-        let (salt, token) = self.generate_auth_params();
-
-        let url = format!(
-            "{}/rest/getPlaylists?u={}&t={}&s={}&v=1.16.1&c=playlist-generator&f=json",
-            self.base_url,
-            encode(&self.username),
-            token,
-            salt
-        );
 
-        println!("Getting playlists from: {url}");
-
-        let response = self.agent.get(&url).call()?;
-        let response_text = response.into_string()?;
+        Ok(all_songs)
+    }
 
-        // println!("Playlists response: {}", response_text);
+    /// Page through getAlbumList2 until the server runs out of albums
+    pub fn fetch_all_albums(&self) -> Result<Vec<AlbumInfo>> {
+        let mut albums = Vec::new();
+        let mut offset = 0;
 
-        let parsed_response: GetPlaylistsResponse = serde_json::from_str(&response_text)?;
+        loop {
+            let page = self.fetch_album_list_page(offset, ALBUM_LIST_PAGE_SIZE)?;
+            let page_len = page.len() as u32;
+            albums.extend(page);
 
-        // Check if the response was successful
-        if parsed_response.subsonic_response.status != "ok" {
-            return Err(anyhow::anyhow!("API error: Response status was not 'ok'"));
+            if page_len < ALBUM_LIST_PAGE_SIZE {
+                break;
+            }
+            offset += page_len;
         }
 
-        // Extract playlists from response
-        match parsed_response.subsonic_response.playlists {
-            Some(playlists_container) => Ok(playlists_container.playlist),
-            None => Ok(vec![]),
-        }
+        Ok(albums)
     }
 
-    /// Create a new playlist or update existing one, deleting any playlists that start with the base name
-    pub fn create_playlist_with_pattern_cleanup(
-        &self,
-        name: &str,
-        base_name_pattern: &str,
-        song_ids: &[String],
-    ) -> Result<String> {
-        // First, check for existing playlists that start with the base pattern and get their ID
-        if let Ok(existing_playlists) = self.get_playlists() {
-            let matching_playlists: Vec<_> = existing_playlists
-                .iter()
-                .filter(|p| {
-                    p.name
-                        .to_lowercase()
-                        .starts_with(base_name_pattern.to_lowercase().as_str())
-                })
-                .collect();
+    /// Internal helper to fetch a single page of albums ordered by name
+    fn fetch_album_list_page(&self, offset: u32, size: u32) -> Result<Vec<AlbumInfo>> {
+        let payload: AlbumListPayload = self.fetch(
+            "getAlbumList2",
+            &[
+                ("type", "alphabeticalByName".to_string()),
+                ("size", size.to_string()),
+                ("offset", offset.to_string()),
+            ],
+        )?;
+
+        Ok(payload
+            .album_list
+            .map(|album_list| album_list.album)
+            .unwrap_or_default())
+    }
 
-            if let Some(existing) = matching_playlists.first() {
-                println!(
-                    "Found existing playlist '{}' matching pattern '{}' (ID: {})",
-                    existing.name, base_name_pattern, existing.id
-                );
-                // Update the existing playlist with new songs
-                return self.update_playlist(&existing.id, name, song_ids);
-            }
-        }
+    /// Fetch every starred song, across all albums
+    pub fn fetch_starred_songs(&self) -> Result<Vec<Song>> {
+        let payload: StarredPayload = self.fetch("getStarred2", &[])?;
 
-        // If no matching playlist, create a new one
-        self.create_playlist(name, song_ids)
+        Ok(payload
+            .starred
+            .map(|starred| starred.song)
+            .unwrap_or_default())
     }
 
-    /// Create a new playlist or overwrite existing one
-    pub fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<String> {
-        // First, check if playlist already exists and delete it
-        if let Ok(existing_playlists) = self.get_playlists() {
-            if let Some(existing) = existing_playlists.iter().find(|p| p.name == name) {
-                println!(
-                    "Playlist '{}' already exists (ID: {}), deleting it first...",
-                    name, existing.id
-                );
-                if let Err(e) = self.delete_playlist(&existing.id) {
-                    println!("Warning: Failed to delete existing playlist: {e}");
-                }
-            }
-        }
+    /// Fetch all songs on a single album
+    pub fn fetch_album_songs(&self, album_id: &str) -> Result<Vec<Song>> {
+        let payload: AlbumPayload = self.fetch("getAlbum", &[("id", album_id.to_string())])?;
 
-        let (salt, token) = self.generate_auth_params();
+        Ok(self.with_genre_support(payload.album.map(|album| album.song).unwrap_or_default()))
+    }
 
-        // Create the playlist with songs
-        let mut url = format!(
-            "{}/rest/createPlaylist?u={}&t={}&s={}&v=1.16.1&c=playlist-generator&f=json&name={}",
-            self.base_url,
-            encode(&self.username),
-            token,
-            salt,
-            encode(name)
-        );
+    /// Get all existing playlists
+    pub fn get_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        let payload: PlaylistsPayload = self.fetch("getPlaylists", &[])?;
 
-        // Add song IDs to the URL
-        for song_id in song_ids {
-            url.push_str(&format!("&songId={}", encode(song_id)));
-        }
+        // Extract playlists from response
+        Ok(payload
+            .playlists
+            .map(|playlists_container| playlists_container.playlist)
+            .unwrap_or_default())
+    }
+
+    /// Create a new playlist. Existing playlists with the same name are left alone;
+    /// replacing a generated playlist is up to the caller.
+    pub fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<String> {
+        // Remember what exists so a retry can tell whether this create went through
+        let existing_playlists = self.get_playlists().unwrap_or_default();
 
         println!(
             "Creating playlist '{}' with {} songs...",
             name,
             song_ids.len()
         );
-        println!("Create playlist URL: {url}");
-
-        let response = self.agent.get(&url).call()?;
-        let response_text = response.into_string()?;
 
-        let parsed_response: CreatePlaylistResponse = serde_json::from_str(&response_text)?;
-
-        if parsed_response.subsonic_response.status != "ok" {
-            return Err(anyhow::anyhow!("API error: Response status was not 'ok'"));
-        }
-
-        match parsed_response.subsonic_response.playlist {
-            Some(playlist) => {
-                println!(
-                    "✓ Successfully created playlist '{}' with ID: {}",
-                    name, playlist.id
-                );
-                Ok(playlist.id)
+        // Without form POST every song ID goes into the URL, so only send the first
+        // chunk with createPlaylist and append the rest with chunked updates
+        let use_form_post = self.capabilities().supports_form_post();
+        let (first_chunk, remaining) = if use_form_post {
+            (song_ids, &[][..])
+        } else {
+            song_ids.split_at(song_ids.len().min(GET_CHUNK_SIZE))
+        };
+
+        let mut params = vec![("name", name.to_string())];
+        params.extend(first_chunk.iter().map(|id| ("songId", id.clone())));
+
+        // createPlaylist is not idempotent: if the connection drops after the server
+        // created the playlist, a blind retry would leave a duplicate behind
+        let known_ids: HashSet<&str> = existing_playlists.iter().map(|p| p.id.as_str()).collect();
+        let payload = self.send_with_retry_check(
+            "createPlaylist",
+            || {
+                self.send_write("createPlaylist", &params)
+                    .and_then(|text| Self::parse_response::<CreatePlaylistPayload>(&text))
+            },
+            || {
+                let created = self
+                    .get_playlists()?
+                    .into_iter()
+                    .find(|p| p.name == name && !known_ids.contains(p.id.as_str()));
+                Ok(match created {
+                    Some(playlist) => RetryCheck::Done(CreatePlaylistPayload {
+                        playlist: Some(playlist.into()),
+                    }),
+                    None => RetryCheck::Retry,
+                })
+            },
+        )?;
+
+        let Some(playlist) = payload.playlist else {
+            return Err(SubsonicError::InvalidResponse(
+                "No playlist returned in create response".to_string(),
+            )
+            .into());
+        };
+
+        let mut current_ids = first_chunk.to_vec();
+        for chunk in remaining.chunks(GET_CHUNK_SIZE) {
+            let edit = PlaylistEdit {
+                remove_indexes: Vec::new(),
+                add_ids: chunk.to_vec(),
+            };
+            match self.apply_playlist_edit(&playlist.id, name, &current_ids, &edit) {
+                Ok(ids) => current_ids = ids,
+                Err(e) => {
+                    // A half-filled playlist is worse than none, so take it down again
+                    if let Err(delete_error) = self.delete_playlist(&playlist.id) {
+                        println!("Warning: Failed to remove incomplete playlist: {delete_error}");
+                    }
+                    return Err(e.context("Failed to add songs to new playlist"));
+                }
             }
-            None => Err(anyhow::anyhow!("No playlist returned in create response")),
         }
+
+        println!(
+            "✓ Successfully created playlist '{}' with ID: {}",
+            name, playlist.id
+        );
+        Ok(playlist.id)
     }
 
     /// Update an existing playlist with new songs
@@ -325,123 +499,486 @@ impl SubsonicClient {
         name: &str,
         song_ids: &[String],
     ) -> Result<String> {
-        let (salt, token) = self.generate_auth_params();
-
         // Fetch current playlist songs to remove all tracks
-        let get_url = format!(
-            "{}/rest/getPlaylist?u={}&t={}&s={}&v=1.16.1&c=playlist-generator&f=json&id={}",
-            self.base_url,
-            encode(&self.username),
-            token,
-            salt,
-            encode(playlist_id)
-        );
-        let list_resp = self.agent.get(&get_url).call()?;
-        let list_text = list_resp.into_string()?;
-        let list_json: serde_json::Value = serde_json::from_str(&list_text)?;
-        // Extract existing songs array
-        let existing = list_json["subsonic-response"]["playlist"]
-            .get("entry")
-            .and_then(|s| s.as_array())
-            .cloned()
-            .unwrap_or_else(Vec::new);
-
-        let mut url = format!(
-            "{}/rest/updatePlaylist?u={}&t={}&s={}&v=1.16.1&c=playlist-generator&f=json&playlistId={}&name={}",
-            self.base_url,
-            encode(&self.username),
-            token,
-            salt,
-            encode(playlist_id),
-            encode(name)
-        );
+        let existing_ids: Vec<String> = self
+            .get_playlist_entries(playlist_id)?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect();
+
         println!(
             "Updating playlist '{}' (ID: {}) with {} existing songs to remove...",
             name,
             playlist_id,
-            existing.len()
+            existing_ids.len()
         );
-        // Remove all existing tracks by index in descending order
-        for idx in 0..existing.len() {
-            url.push_str(&format!("&songIndexToRemove={idx}"));
-        }
-        // Append new songs
-        for song_id in song_ids {
-            url.push_str(&format!("&songIdToAdd={}", encode(song_id)));
-        }
         println!(
             "Updating playlist '{}' (ID: {}) with {} songs...",
             name,
             playlist_id,
             song_ids.len()
         );
-        println!("Update playlist URL: {url}");
-
-        let response = self.agent.get(&url).call()?;
-        let response_text = response.into_string()?;
-
-        let parsed: serde_json::Value = serde_json::from_str(&response_text)?;
-        if let Some(status) = parsed
-            .get("subsonic-response")
-            .and_then(|r| r.get("status"))
-            .and_then(|s| s.as_str())
-        {
-            if status == "ok" {
-                println!("✓ Successfully updated playlist '{name}' (ID: {playlist_id})");
-                Ok(playlist_id.to_string())
-            } else {
-                Err(anyhow::anyhow!(
-                    "API error: Update playlist status was not 'ok': {}",
-                    status
-                ))
-            }
+
+        let edits = if self.capabilities().supports_form_post() {
+            // Remove all existing tracks and append the new ones in a single request
+            vec![PlaylistEdit {
+                remove_indexes: (0..existing_ids.len()).collect(),
+                add_ids: song_ids.to_vec(),
+            }]
         } else {
-            Err(anyhow::anyhow!(
-                "Invalid response format from update playlist"
-            ))
+            Self::chunked_replacement(existing_ids.len(), song_ids)
+        };
+
+        let mut current_ids = existing_ids;
+        for edit in &edits {
+            current_ids = self
+                .apply_playlist_edit(playlist_id, name, &current_ids, edit)
+                .context("Failed to update playlist")?;
         }
+
+        println!("✓ Successfully updated playlist '{name}' (ID: {playlist_id})");
+        Ok(playlist_id.to_string())
     }
 
-    /// Delete an existing playlist
-    pub fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
-        let (salt, token) = self.generate_auth_params();
-
-        let url = format!(
-            "{}/rest/deletePlaylist?u={}&t={}&s={}&v=1.16.1&c=playlist-generator&f=json&id={}",
-            self.base_url,
-            encode(&self.username),
-            token,
-            salt,
-            encode(playlist_id)
+    /// Split a full replacement into URL-sized edits. The new songs are appended before
+    /// any old track is removed, so the playlist is never empty between requests. Old
+    /// tracks are then removed from the highest index down, keeping the rest valid.
+    fn chunked_replacement(existing_len: usize, song_ids: &[String]) -> Vec<PlaylistEdit> {
+        let additions = song_ids.chunks(GET_CHUNK_SIZE).map(|chunk| PlaylistEdit {
+            remove_indexes: Vec::new(),
+            add_ids: chunk.to_vec(),
+        });
+        let indexes: Vec<usize> = (0..existing_len).rev().collect();
+        let removals = indexes.chunks(GET_CHUNK_SIZE).map(|chunk| PlaylistEdit {
+            remove_indexes: chunk.to_vec(),
+            add_ids: Vec::new(),
+        });
+        additions.chain(removals).collect()
+    }
+
+    /// Apply one updatePlaylist call and return the playlist contents it should produce
+    fn apply_playlist_edit(
+        &self,
+        playlist_id: &str,
+        name: &str,
+        before_ids: &[String],
+        edit: &PlaylistEdit,
+    ) -> Result<Vec<String>> {
+        let after_ids = edit.apply(before_ids);
+
+        let mut params = vec![
+            ("playlistId", playlist_id.to_string()),
+            ("name", name.to_string()),
+        ];
+        params.extend(
+            edit.remove_indexes
+                .iter()
+                .map(|idx| ("songIndexToRemove", idx.to_string())),
         );
+        params.extend(edit.add_ids.iter().map(|id| ("songIdToAdd", id.clone())));
+
+        // updatePlaylist removes tracks by index, so repeating a request the server already
+        // applied would delete the wrong tracks. Only retry when the playlist is untouched.
+        self.send_with_retry_check(
+            "updatePlaylist",
+            || {
+                self.send_write("updatePlaylist", &params)
+                    .and_then(|text| Self::parse_response::<EmptyPayload>(&text))
+            },
+            || {
+                let current = self.get_playlist_entries(playlist_id)?;
+                let current_ids: Vec<&str> = current.iter().map(|e| e.id.as_str()).collect();
+                if current_ids == after_ids {
+                    Ok(RetryCheck::Done(EmptyPayload {}))
+                } else if current_ids == before_ids {
+                    Ok(RetryCheck::Retry)
+                } else {
+                    Err(anyhow::anyhow!(
+                        "playlist '{name}' was partially updated and is left with {} songs",
+                        current_ids.len()
+                    ))
+                }
+            },
+        )?;
+
+        Ok(after_ids)
+    }
+
+    /// Set the comment and visibility of a playlist without touching its songs.
+    /// Safe to repeat, so it is retried like a read.
+    pub fn update_playlist_details(
+        &self,
+        playlist_id: &str,
+        comment: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        let mut params = vec![("playlistId", playlist_id.to_string())];
+        if let Some(comment) = comment {
+            params.push(("comment", comment.to_string()));
+        }
+        if let Some(public) = public {
+            params.push(("public", public.to_string()));
+        }
+
+        self.fetch::<EmptyPayload>("updatePlaylist", &params)
+            .context("Failed to update playlist details")?;
+        Ok(())
+    }
+
+    /// Rename a playlist without touching its songs
+    pub fn rename_playlist(&self, playlist_id: &str, name: &str) -> Result<()> {
+        let params = [
+            ("playlistId", playlist_id.to_string()),
+            ("name", name.to_string()),
+        ];
+        self.fetch::<EmptyPayload>("updatePlaylist", &params)
+            .context("Failed to rename playlist")?;
+        Ok(())
+    }
 
+    /// Delete an existing playlist
+    pub fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
         println!("Deleting playlist ID: {playlist_id}");
 
-        let response = self.agent.get(&url).call()?;
-        let response_text = response.into_string()?;
+        let response_text = self.send(
+            "deletePlaylist",
+            &[("id", playlist_id.to_string())],
+            Method::Get,
+        )?;
 
         // println!("Delete playlist response: {}", response_text);
 
         // For delete, we just need to check the status
-        let parsed_response: serde_json::Value = serde_json::from_str(&response_text)?;
-
-        if let Some(status) = parsed_response
-            .get("subsonic-response")
-            .and_then(|r| r.get("status"))
-            .and_then(|s| s.as_str())
-        {
-            if status == "ok" {
-                println!("✓ Successfully deleted playlist");
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "API error: Delete playlist status was not 'ok'"
-                ))
+        Self::parse_response::<EmptyPayload>(&response_text)?;
+
+        println!("✓ Successfully deleted playlist");
+        Ok(())
+    }
+
+    /// List the OpenSubsonic extensions supported by the server
+    pub fn get_open_subsonic_extensions(&self) -> Result<Vec<OpenSubsonicExtension>> {
+        let payload: ExtensionsPayload = self.fetch("getOpenSubsonicExtensions", &[])?;
+        Ok(payload.extensions)
+    }
+
+    /// Fetch the tracks currently in a playlist
+    pub fn get_playlist_entries(&self, playlist_id: &str) -> Result<Vec<PlaylistEntry>> {
+        let payload: PlaylistPayload =
+            self.fetch("getPlaylist", &[("id", playlist_id.to_string())])?;
+
+        Ok(payload
+            .playlist
+            .map(|playlist| playlist.entry)
+            .unwrap_or_default())
+    }
+
+    /// Fetch the tracks of a playlist with their full metadata. Entries that do not
+    /// parse as songs are skipped.
+    pub fn get_playlist_songs(&self, playlist_id: &str) -> Result<Vec<Song>> {
+        let payload: PlaylistSongsPayload =
+            self.fetch("getPlaylist", &[("id", playlist_id.to_string())])?;
+
+        Ok(self.with_genre_support(
+            payload
+                .playlist
+                .map(|playlist| playlist.entry)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|entry| serde_json::from_value(entry).ok())
+                .collect(),
+        ))
+    }
+
+    /// Send an idempotent GET request with retries and parse the response envelope
+    fn fetch<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, SubsonicError> {
+        let response_text = self.send_idempotent(endpoint, params)?;
+        Self::parse_response(&response_text)
+    }
+
+    /// Send a GET request that is safe to repeat, retrying transient failures with backoff
+    fn send_idempotent(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<String, SubsonicError> {
+        let mut attempt = 0;
+        loop {
+            match self.send(endpoint, params, Method::Get) {
+                Err(e) if e.is_retryable() && attempt < self.http.max_retries => {
+                    attempt += 1;
+                    self.wait_before_retry(endpoint, attempt, &e);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Run a request that is not idempotent. After a transient failure `check` inspects the
+    /// server state to decide whether the request already took effect or can be sent again.
+    fn send_with_retry_check<T>(
+        &self,
+        endpoint: &str,
+        mut request: impl FnMut() -> Result<T, SubsonicError>,
+        mut check: impl FnMut() -> Result<RetryCheck<T>>,
+    ) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match request() {
+                Err(e) if e.is_retryable() && attempt < self.http.max_retries => {
+                    match check().with_context(|| format!("{endpoint} failed: {e}"))? {
+                        RetryCheck::Done(value) => {
+                            println!(
+                                "{endpoint} reported an error ({e}) but the change was applied"
+                            );
+                            return Ok(value);
+                        }
+                        RetryCheck::Retry => {
+                            attempt += 1;
+                            self.wait_before_retry(endpoint, attempt, &e);
+                        }
+                    }
+                }
+                result => return result.map_err(Into::into),
             }
+        }
+    }
+
+    /// Log a failed attempt and sleep for the exponential backoff delay
+    fn wait_before_retry(&self, endpoint: &str, attempt: u32, error: &SubsonicError) {
+        let delay = self
+            .http
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(MAX_BACKOFF);
+        println!(
+            "  {endpoint} failed ({error}), retrying in {:.1}s (attempt {}/{})",
+            delay.as_secs_f32(),
+            attempt,
+            self.http.max_retries
+        );
+        std::thread::sleep(delay);
+    }
+
+    /// Authentication and protocol parameters sent with every request
+    fn common_params(auth: &Auth) -> Vec<(&'static str, String)> {
+        let mut params = Self::auth_params(auth);
+        params.extend([
+            ("v", API_VERSION.to_string()),
+            ("c", CLIENT_NAME.to_string()),
+            ("f", "json".to_string()),
+        ]);
+        params
+    }
+
+    /// Send a playlist write. Uses a form-encoded POST body when the server supports
+    /// the `formPost` extension so long song lists never hit URL length limits.
+    fn send_write(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+    ) -> Result<String, SubsonicError> {
+        let method = if self.capabilities().supports_form_post() {
+            Method::FormPost
         } else {
-            Err(anyhow::anyhow!(
-                "Invalid response format from delete playlist"
-            ))
+            Method::Get
+        };
+        self.send(endpoint, params, method)
+    }
+
+    /// Send a single request. Every endpoint goes through here: it adds authentication,
+    /// logs the request with credentials redacted, and returns the response body.
+    fn send(
+        &self,
+        endpoint: &str,
+        params: &[(&str, String)],
+        method: Method,
+    ) -> Result<String, SubsonicError> {
+        let common = Self::common_params(&self.active_auth());
+        let pairs: Vec<(&str, &str)> = common
+            .iter()
+            .chain(params.iter())
+            .map(|(key, value)| (*key, value.as_str()))
+            .collect();
+        let url = format!("{}/rest/{}", self.base_url.trim_end_matches('/'), endpoint);
+
+        self.log_request(endpoint, &url, &pairs, method);
+
+        match method {
+            Method::Get => {
+                let query: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, encode(value)))
+                    .collect();
+                Self::read_response(
+                    self.agent
+                        .get(&format!("{}?{}", url, query.join("&")))
+                        .call(),
+                )
+            }
+            Method::FormPost => Self::read_response(self.agent.post(&url).send_form(&pairs)),
+        }
+    }
+
+    /// Print a request according to the configured log mode. Summary mode shows only
+    /// playlist changes, with long repeated parameters collapsed; verbose mode shows the
+    /// full URL or form body of every request.
+    fn log_request(&self, endpoint: &str, url: &str, pairs: &[(&str, &str)], method: Method) {
+        let verbose = match self.request_log {
+            RequestLog::Off => return,
+            RequestLog::Summary if !WRITE_ENDPOINTS.contains(&endpoint) => return,
+            RequestLog::Summary => false,
+            RequestLog::Verbose => true,
+        };
+
+        if verbose {
+            let query: Vec<String> = pairs
+                .iter()
+                .map(|(key, value)| {
+                    if CREDENTIAL_PARAMS.contains(key) {
+                        format!("{key}=***")
+                    } else {
+                        format!("{}={}", key, encode(value))
+                    }
+                })
+                .collect();
+            match method {
+                Method::Get => println!("  GET {}?{}", url, query.join("&")),
+                Method::FormPost => println!("  POST {} [{}]", url, query.join("&")),
+            }
+            return;
+        }
+
+        // Group repeated parameters such as songId, skipping the ones every request carries
+        let mut summary: Vec<(&str, Vec<&str>)> = Vec::new();
+        for &(key, value) in pairs {
+            if CREDENTIAL_PARAMS.contains(&key) || PROTOCOL_PARAMS.contains(&key) {
+                continue;
+            }
+            match summary.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(value),
+                None => summary.push((key, vec![value])),
+            }
+        }
+        let params: Vec<String> = summary
+            .iter()
+            .map(|(key, values)| match values.as_slice() {
+                [value] => format!("{key}={value}"),
+                values => format!("{key}=[{} values]", values.len()),
+            })
+            .collect();
+        println!("  → {} {}", endpoint, params.join(" "));
+    }
+
+    /// Turn a ureq result into the response body or a typed error
+    fn read_response(result: Result<ureq::Response, ureq::Error>) -> Result<String, SubsonicError> {
+        match result {
+            Ok(response) => response
+                .into_string()
+                .map_err(|e| SubsonicError::Transport(e.to_string())),
+            Err(ureq::Error::Status(status, response)) => Err(SubsonicError::Http {
+                status,
+                message: response.status_text().to_string(),
+            }),
+            // Not `transport.to_string()`: that includes the request URL and its credentials
+            Err(ureq::Error::Transport(transport)) => {
+                let mut message = transport.kind().to_string();
+                if let Some(detail) = transport.message() {
+                    message.push_str(&format!(": {detail}"));
+                }
+                if let Some(source) = std::error::Error::source(&transport) {
+                    message.push_str(&format!(": {source}"));
+                }
+                Err(SubsonicError::Transport(message))
+            }
+        }
+    }
+
+    /// Parse a Subsonic response envelope, turning a "failed" status into a typed error
+    fn parse_response<T: DeserializeOwned>(response_text: &str) -> Result<T, SubsonicError> {
+        Self::parse_full_response(response_text).map(|response| response.payload)
+    }
+
+    /// Like `parse_response`, but keep the common fields such as the API version
+    fn parse_full_response<T: DeserializeOwned>(
+        response_text: &str,
+    ) -> Result<SubsonicResponse<T>, SubsonicError> {
+        let envelope: SubsonicEnvelope<T> = serde_json::from_str(response_text)
+            .map_err(|e| SubsonicError::InvalidResponse(e.to_string()))?;
+        let response = envelope.subsonic_response;
+
+        if response.status == "ok" {
+            return Ok(response);
         }
+
+        Err(match response.error {
+            Some(error) => SubsonicError::from_api(error.code, error.message),
+            None => SubsonicError::UnspecifiedFailure,
+        })
+    }
+}
+
+impl MusicSource for SubsonicClient {
+    fn ping(&self) -> Result<()> {
+        SubsonicClient::ping(self).map(|_| ())
+    }
+
+    fn fetch_songs(&self, count: Option<u32>) -> Result<Vec<Song>> {
+        SubsonicClient::fetch_songs(self, count)
+    }
+
+    fn fetch_all_songs(&self, on_progress: &mut dyn FnMut(&LibraryProgress)) -> Result<Vec<Song>> {
+        SubsonicClient::fetch_all_songs(self, on_progress)
+    }
+}
+
+impl PlaylistSink for SubsonicClient {
+    fn list_playlists(&self) -> Result<Vec<PlaylistInfo>> {
+        self.get_playlists()
+    }
+
+    fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<String> {
+        SubsonicClient::create_playlist(self, name, song_ids)
+    }
+
+    fn update_playlist(
+        &self,
+        playlist_id: &str,
+        name: &str,
+        song_ids: &[String],
+    ) -> Result<String> {
+        SubsonicClient::update_playlist(self, playlist_id, name, song_ids)
+    }
+
+    fn playlist_song_ids(&self, playlist_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .get_playlist_entries(playlist_id)?
+            .into_iter()
+            .map(|entry| entry.id)
+            .collect())
+    }
+
+    fn playlist_songs(&self, playlist_id: &str) -> Result<Vec<Song>> {
+        self.get_playlist_songs(playlist_id)
+    }
+
+    fn delete_playlist(&self, playlist_id: &str) -> Result<()> {
+        SubsonicClient::delete_playlist(self, playlist_id)
+    }
+
+    fn rename_playlist(&self, playlist_id: &str, name: &str) -> Result<()> {
+        SubsonicClient::rename_playlist(self, playlist_id, name)
+    }
+
+    fn update_playlist_details(
+        &self,
+        playlist_id: &str,
+        comment: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()> {
+        SubsonicClient::update_playlist_details(self, playlist_id, comment, public)
     }
 }
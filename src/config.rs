@@ -1,11 +1,166 @@
 use anyhow::Result;
+use std::fmt;
+use std::time::Duration;
 
 /// Configuration loaded from environment variables
 #[derive(Debug)]
 pub struct Config {
     pub base_url: String,
-    pub username: String,
-    pub password: String,
+    pub auth: Auth,
+    pub http: HttpPolicy,
+    pub request_log: RequestLog,
+}
+
+/// How much of each request the client prints. Credentials are always redacted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RequestLog {
+    /// Nothing
+    Off,
+    /// One line per playlist change, with repeated parameters collapsed
+    #[default]
+    Summary,
+    /// The full URL or form body of every request
+    Verbose,
+}
+
+impl RequestLog {
+    /// Read `REQUEST_LOG` from the environment
+    fn from_env() -> Result<Self> {
+        match std::env::var("REQUEST_LOG") {
+            Ok(value) => match value.trim().to_lowercase().as_str() {
+                "off" => Ok(RequestLog::Off),
+                "summary" => Ok(RequestLog::Summary),
+                "verbose" => Ok(RequestLog::Verbose),
+                _ => Err(anyhow::anyhow!(
+                    "REQUEST_LOG must be one of off, summary or verbose, got '{value}'"
+                )),
+            },
+            Err(_) => Ok(RequestLog::default()),
+        }
+    }
+}
+
+/// How requests authenticate with the server. The same method is used for every endpoint.
+#[derive(Clone)]
+pub enum Auth {
+    /// OpenSubsonic `apiKeyAuthentication`: a revocable key sent as `apiKey`, no username
+    ApiKey(String),
+    /// Salted MD5 token derived from the password (API 1.13.0+)
+    Token { username: String, password: String },
+    /// Legacy hex-encoded password (`p=enc:...`) for servers or users without token support
+    EncodedPassword { username: String, password: String },
+}
+
+impl Auth {
+    /// Short name of the method, as accepted by `AUTH_METHOD`
+    pub fn method_name(&self) -> &'static str {
+        match self {
+            Auth::ApiKey(_) => "api-key",
+            Auth::Token { .. } => "token",
+            Auth::EncodedPassword { .. } => "password",
+        }
+    }
+
+    /// Read credentials from the environment. `AUTH_METHOD` picks the method explicitly;
+    /// otherwise an `API_KEY` wins over `USERNAME`/`PASSWORD` token auth.
+    fn from_env() -> Result<Self> {
+        let api_key = std::env::var("API_KEY").ok().filter(|key| !key.is_empty());
+        let method = match std::env::var("AUTH_METHOD") {
+            Ok(method) => method.trim().to_lowercase(),
+            Err(_) if api_key.is_some() => "api-key".to_string(),
+            Err(_) => "token".to_string(),
+        };
+
+        let password_credentials = || -> Result<(String, String)> {
+            let username = std::env::var("USERNAME")
+                .map_err(|_| anyhow::anyhow!("USERNAME must be set for {method} authentication"))?;
+            let password = std::env::var("PASSWORD")
+                .map_err(|_| anyhow::anyhow!("PASSWORD must be set for {method} authentication"))?;
+            Ok((username, password))
+        };
+
+        match method.as_str() {
+            "api-key" => api_key
+                .map(Auth::ApiKey)
+                .ok_or_else(|| anyhow::anyhow!("API_KEY must be set for api-key authentication")),
+            "token" => {
+                let (username, password) = password_credentials()?;
+                Ok(Auth::Token { username, password })
+            }
+            "password" => {
+                let (username, password) = password_credentials()?;
+                Ok(Auth::EncodedPassword { username, password })
+            }
+            other => Err(anyhow::anyhow!(
+                "AUTH_METHOD must be one of api-key, token or password, got '{other}'"
+            )),
+        }
+    }
+}
+
+// Keep secrets out of debug output
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::ApiKey(_) => write!(f, "ApiKey(..)"),
+            Auth::Token { username, .. } | Auth::EncodedPassword { username, .. } => {
+                write!(f, "{}({username})", self.method_name())
+            }
+        }
+    }
+}
+
+/// Timeouts and retry behaviour for requests to the server
+#[derive(Debug, Clone)]
+pub struct HttpPolicy {
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub max_retries: u32,          // Extra attempts after the first one fails
+    pub initial_backoff: Duration, // Doubled after every failed attempt
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            read_timeout: Duration::from_secs(60),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl HttpPolicy {
+    /// Read overrides from the environment, keeping defaults for anything unset
+    fn from_env() -> Result<Self> {
+        let defaults = Self::default();
+        Ok(Self {
+            connect_timeout: Duration::from_secs(env_or(
+                "HTTP_CONNECT_TIMEOUT_SECS",
+                defaults.connect_timeout.as_secs(),
+            )?),
+            read_timeout: Duration::from_secs(env_or(
+                "HTTP_READ_TIMEOUT_SECS",
+                defaults.read_timeout.as_secs(),
+            )?),
+            max_retries: env_or("HTTP_MAX_RETRIES", defaults.max_retries)?,
+            initial_backoff: Duration::from_millis(env_or(
+                "HTTP_RETRY_BACKOFF_MS",
+                defaults.initial_backoff.as_millis() as u64,
+            )?),
+        })
+    }
+}
+
+/// Parse an optional numeric environment variable
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> Result<T> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("{name} must be a number, got '{value}'")),
+        Err(_) => Ok(default),
+    }
 }
 
 /// Load configuration from `.env` and environment
@@ -14,11 +169,13 @@ pub fn load_config() -> Result<Config> {
     dotenv::dotenv().ok();
     // Read variables
     let base_url = std::env::var("BASE_URL")?;
-    let username = std::env::var("USERNAME")?;
-    let password = std::env::var("PASSWORD")?;
+    let auth = Auth::from_env()?;
+    let http = HttpPolicy::from_env()?;
+    let request_log = RequestLog::from_env()?;
     Ok(Config {
         base_url,
-        username,
-        password,
+        auth,
+        http,
+        request_log,
     })
 }
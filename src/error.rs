@@ -0,0 +1,199 @@
+use std::fmt;
+
+/// Errors reported by a Subsonic server, or raised while talking to it.
+/// Server-side variants map one-to-one onto the error codes in the Subsonic API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubsonicError {
+    /// Code 0: a generic error
+    Generic(String),
+    /// Code 10: a required parameter is missing
+    MissingParameter(String),
+    /// Code 20: incompatible REST protocol version, the client must upgrade
+    ClientTooOld(String),
+    /// Code 30: incompatible REST protocol version, the server must upgrade
+    ServerTooOld(String),
+    /// Code 40: wrong username or password
+    WrongCredentials(String),
+    /// Code 41: token authentication is not supported for this user (e.g. LDAP)
+    TokenAuthNotSupported(String),
+    /// Code 42: the provided authentication mechanism is not supported
+    AuthMechanismNotSupported(String),
+    /// Code 43: multiple conflicting authentication mechanisms were provided
+    ConflictingAuthMechanisms(String),
+    /// Code 44: invalid API key
+    InvalidApiKey(String),
+    /// Code 50: the user is not authorized for the given operation
+    NotAuthorized(String),
+    /// Code 60: the trial period for the server is over
+    TrialExpired(String),
+    /// Code 70: the requested data was not found
+    NotFound(String),
+    /// Any error code not covered above
+    Other { code: u32, message: String },
+    /// Status was "failed" but the response carried no error object
+    UnspecifiedFailure,
+    /// The server answered with a non-success HTTP status
+    Http { status: u16, message: String },
+    /// The request never got a response (DNS, connection refused, timeout, ...)
+    Transport(String),
+    /// The response body was not a valid Subsonic response
+    InvalidResponse(String),
+}
+
+impl SubsonicError {
+    /// Build the typed error for a Subsonic `error` object
+    pub fn from_api(code: u32, message: String) -> Self {
+        match code {
+            0 => SubsonicError::Generic(message),
+            10 => SubsonicError::MissingParameter(message),
+            20 => SubsonicError::ClientTooOld(message),
+            30 => SubsonicError::ServerTooOld(message),
+            40 => SubsonicError::WrongCredentials(message),
+            41 => SubsonicError::TokenAuthNotSupported(message),
+            42 => SubsonicError::AuthMechanismNotSupported(message),
+            43 => SubsonicError::ConflictingAuthMechanisms(message),
+            44 => SubsonicError::InvalidApiKey(message),
+            50 => SubsonicError::NotAuthorized(message),
+            60 => SubsonicError::TrialExpired(message),
+            70 => SubsonicError::NotFound(message),
+            code => SubsonicError::Other { code, message },
+        }
+    }
+
+    /// The Subsonic error code, if the error came from the server's `error` object
+    pub fn code(&self) -> Option<u32> {
+        match self {
+            SubsonicError::Generic(_) => Some(0),
+            SubsonicError::MissingParameter(_) => Some(10),
+            SubsonicError::ClientTooOld(_) => Some(20),
+            SubsonicError::ServerTooOld(_) => Some(30),
+            SubsonicError::WrongCredentials(_) => Some(40),
+            SubsonicError::TokenAuthNotSupported(_) => Some(41),
+            SubsonicError::AuthMechanismNotSupported(_) => Some(42),
+            SubsonicError::ConflictingAuthMechanisms(_) => Some(43),
+            SubsonicError::InvalidApiKey(_) => Some(44),
+            SubsonicError::NotAuthorized(_) => Some(50),
+            SubsonicError::TrialExpired(_) => Some(60),
+            SubsonicError::NotFound(_) => Some(70),
+            SubsonicError::Other { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Whether the server rejected the credentials or the way they were sent
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(
+            self,
+            SubsonicError::WrongCredentials(_)
+                | SubsonicError::TokenAuthNotSupported(_)
+                | SubsonicError::AuthMechanismNotSupported(_)
+                | SubsonicError::ConflictingAuthMechanisms(_)
+                | SubsonicError::InvalidApiKey(_)
+        )
+    }
+
+    /// Whether the client and server disagree on the REST protocol version
+    pub fn is_version_mismatch(&self) -> bool {
+        matches!(
+            self,
+            SubsonicError::ClientTooOld(_) | SubsonicError::ServerTooOld(_)
+        )
+    }
+
+    /// Whether the failure is likely transient and the request worth repeating
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            SubsonicError::Transport(_) => true,
+            SubsonicError::Http { status, .. } => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Short human-readable hint on how to fix the error, for cron logs
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            SubsonicError::InvalidApiKey(_) => Some("check API_KEY in .env"),
+            SubsonicError::TokenAuthNotSupported(_) => {
+                Some("set AUTH_METHOD=password or use an API_KEY")
+            }
+            SubsonicError::AuthMechanismNotSupported(_) => {
+                Some("the server does not accept this AUTH_METHOD, run server-info to check")
+            }
+            SubsonicError::ConflictingAuthMechanisms(_) => {
+                Some("set either API_KEY or USERNAME and PASSWORD in .env, not both")
+            }
+            SubsonicError::WrongCredentials(_) => Some("check USERNAME and PASSWORD in .env"),
+            e if e.is_version_mismatch() => {
+                Some("the server and client API versions are incompatible")
+            }
+            SubsonicError::NotAuthorized(_) => Some("the user lacks permission for this operation"),
+            SubsonicError::NotFound(_) => Some("the item no longer exists on the server"),
+            SubsonicError::Transport(_) => Some("check BASE_URL and that the server is reachable"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SubsonicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubsonicError::Generic(message) => write!(f, "Subsonic error 0 (generic): {message}"),
+            SubsonicError::MissingParameter(message) => {
+                write!(f, "Subsonic error 10 (missing parameter): {message}")
+            }
+            SubsonicError::ClientTooOld(message) => {
+                write!(f, "Subsonic error 20 (client must upgrade): {message}")
+            }
+            SubsonicError::ServerTooOld(message) => {
+                write!(f, "Subsonic error 30 (server must upgrade): {message}")
+            }
+            SubsonicError::WrongCredentials(message) => {
+                write!(
+                    f,
+                    "Subsonic error 40 (wrong username or password): {message}"
+                )
+            }
+            SubsonicError::TokenAuthNotSupported(message) => {
+                write!(
+                    f,
+                    "Subsonic error 41 (token authentication not supported): {message}"
+                )
+            }
+            SubsonicError::AuthMechanismNotSupported(message) => {
+                write!(
+                    f,
+                    "Subsonic error 42 (authentication mechanism not supported): {message}"
+                )
+            }
+            SubsonicError::ConflictingAuthMechanisms(message) => {
+                write!(
+                    f,
+                    "Subsonic error 43 (conflicting authentication mechanisms): {message}"
+                )
+            }
+            SubsonicError::InvalidApiKey(message) => {
+                write!(f, "Subsonic error 44 (invalid API key): {message}")
+            }
+            SubsonicError::NotAuthorized(message) => {
+                write!(f, "Subsonic error 50 (not authorized): {message}")
+            }
+            SubsonicError::TrialExpired(message) => {
+                write!(f, "Subsonic error 60 (trial expired): {message}")
+            }
+            SubsonicError::NotFound(message) => {
+                write!(f, "Subsonic error 70 (not found): {message}")
+            }
+            SubsonicError::Other { code, message } => write!(f, "Subsonic error {code}: {message}"),
+            SubsonicError::UnspecifiedFailure => {
+                write!(f, "Subsonic request failed without an error description")
+            }
+            SubsonicError::Http { status, message } => write!(f, "HTTP error {status}: {message}"),
+            SubsonicError::Transport(message) => write!(f, "HTTP request failed: {message}"),
+            SubsonicError::InvalidResponse(message) => {
+                write!(f, "Failed to parse JSON response: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SubsonicError {}
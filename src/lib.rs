@@ -0,0 +1,25 @@
+//! Playlist generation for OpenSubsonic servers.
+//!
+//! The generator itself only depends on the [`source::MusicSource`] and
+//! [`source::PlaylistSink`] traits. The Subsonic HTTP client (and its ureq
+//! dependency) lives behind the default `subsonic` feature.
+
+pub mod capabilities;
+#[cfg(feature = "subsonic")]
+pub mod client;
+#[cfg(feature = "subsonic")]
+pub mod config;
+pub mod error;
+#[cfg(feature = "subsonic")]
+pub mod library_cache;
+pub mod models;
+pub mod ownership;
+pub mod placement_history;
+pub mod playlist;
+pub mod run_history;
+pub mod run_report;
+pub mod snapshot;
+pub mod source;
+
+#[cfg(test)]
+mod playlist_tests;
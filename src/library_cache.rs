@@ -0,0 +1,180 @@
+use crate::client::SubsonicClient;
+use crate::models::Song;
+use crate::source::LibraryProgress;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Bump this whenever the on-disk layout changes; older files are discarded
+pub const LIBRARY_CACHE_VERSION: u32 = 1;
+
+const LIBRARY_CACHE_FILE: &str = "library-cache.json";
+
+/// Local copy of the server library, stored per album so it can be refreshed incrementally
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LibraryCache {
+    pub version: u32,
+    pub synced_at: Option<String>,
+    pub albums: BTreeMap<String, CachedAlbum>,
+}
+
+/// A single album and its songs as of the last sync
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAlbum {
+    pub fingerprint: String,
+    pub songs: Vec<Song>,
+}
+
+/// Counts of what an incremental refresh did
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RefreshStats {
+    pub unchanged: usize,
+    pub refreshed: usize,
+    pub removed: usize,
+}
+
+impl LibraryCache {
+    /// Create an empty cache with the current version
+    pub fn new() -> Self {
+        Self {
+            version: LIBRARY_CACHE_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Location of the cache file inside the data directory
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(LIBRARY_CACHE_FILE)
+    }
+
+    /// Load the cache from disk. Returns `None` if there is no cache yet, it was
+    /// written by an incompatible version, or it is corrupt. A corrupt file is deleted
+    /// so the next refresh rebuilds it from scratch.
+    pub fn load(data_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(data_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read(&path)?;
+        let cache: LibraryCache = match serde_json::from_slice(&content) {
+            Ok(cache) => cache,
+            Err(e) => {
+                println!(
+                    "Warning: Library cache {} is corrupt ({e}), discarding it and rebuilding",
+                    path.display()
+                );
+                std::fs::remove_file(&path)?;
+                return Ok(None);
+            }
+        };
+
+        if cache.version != LIBRARY_CACHE_VERSION {
+            println!(
+                "Library cache version {} is outdated (expected {}), ignoring it",
+                cache.version, LIBRARY_CACHE_VERSION
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(cache))
+    }
+
+    /// Write the cache to disk, replacing the previous file atomically
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = Self::path(data_dir);
+        let tmp_path = path.with_extension("json.tmp");
+
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// All cached songs, in album order
+    pub fn songs(&self) -> Vec<Song> {
+        self.albums
+            .values()
+            .flat_map(|album| album.songs.iter().cloned())
+            .collect()
+    }
+
+    /// Total number of cached songs
+    pub fn song_count(&self) -> usize {
+        self.albums.values().map(|album| album.songs.len()).sum()
+    }
+
+    /// Bring the cache up to date with the server, only re-fetching albums
+    /// whose created/changed timestamps, user data or starred songs differ from
+    /// the last sync.
+    pub fn refresh<F>(
+        &mut self,
+        client: &SubsonicClient,
+        mut on_progress: F,
+    ) -> Result<RefreshStats>
+    where
+        F: FnMut(&LibraryProgress),
+    {
+        let albums = client.fetch_all_albums()?;
+        let starred_songs = client.fetch_starred_songs()?;
+        let mut starred_by_album: HashMap<&str, Vec<&str>> = HashMap::new();
+        for song in &starred_songs {
+            if let Some(album_id) = &song.album_id {
+                starred_by_album
+                    .entry(album_id.as_str())
+                    .or_default()
+                    .push(song.id.as_str());
+            }
+        }
+        for song_ids in starred_by_album.values_mut() {
+            song_ids.sort_unstable();
+        }
+
+        let mut stats = RefreshStats::default();
+        let mut refreshed_albums = BTreeMap::new();
+        let mut songs_found = 0;
+
+        // Build the new album map on the side so a failed refresh leaves the cache intact
+        for (index, album) in albums.iter().enumerate() {
+            let starred_song_ids = starred_by_album
+                .get(album.id.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let fingerprint = album.fingerprint(starred_song_ids);
+
+            let cached = match self.albums.get(&album.id) {
+                Some(cached) if cached.fingerprint == fingerprint => {
+                    stats.unchanged += 1;
+                    cached.clone()
+                }
+                _ => {
+                    stats.refreshed += 1;
+                    CachedAlbum {
+                        fingerprint,
+                        songs: client.fetch_album_songs(&album.id)?,
+                    }
+                }
+            };
+            songs_found += cached.songs.len();
+            refreshed_albums.insert(album.id.clone(), cached);
+
+            on_progress(&LibraryProgress {
+                albums_scanned: index + 1,
+                albums_total: albums.len(),
+                songs_found,
+            });
+        }
+
+        // Anything not seen on the server any more has been removed
+        stats.removed = self
+            .albums
+            .keys()
+            .filter(|id| !refreshed_albums.contains_key(*id))
+            .count();
+        self.albums = refreshed_albums;
+        self.synced_at = Some(chrono::Utc::now().to_rfc3339());
+
+        Ok(stats)
+    }
+}
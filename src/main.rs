@@ -1,18 +1,27 @@
 use anyhow::Result;
-use clap::Parser;
-
-mod client;
-mod config;
-mod models;
-mod playlist;
-
-#[cfg(test)]
-mod playlist_tests;
-
-use crate::client::SubsonicClient;
-use crate::config::load_config;
-use crate::models::Song;
-use crate::playlist::{PlaylistConfig, PlaylistGenerator};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use playlist_generator::client::SubsonicClient;
+use playlist_generator::config::load_config;
+use playlist_generator::error::SubsonicError;
+use playlist_generator::library_cache::LibraryCache;
+use playlist_generator::models::Song;
+use playlist_generator::placement_history::{Coverage, PlacementHistory};
+use playlist_generator::playlist::filters::SongFilters;
+use playlist_generator::playlist::{
+    Playlist, PlaylistConfig, PlaylistDiff, PlaylistGenerator, RotationSettings,
+};
+use playlist_generator::run_history::{Generation, RunHistory};
+use playlist_generator::run_report::{PlaylistStatus, RunReport};
+use playlist_generator::snapshot::LibrarySnapshot;
+use playlist_generator::source::{
+    LibraryProgress, MusicSource, PlaylistSink, PlaylistUpload, ReconciledDuplicate,
+};
 
 #[derive(Parser)]
 #[command(name = "playlist-generator")]
@@ -30,11 +39,77 @@ struct Args {
     /// Quiet mode - reduce output verbosity
     #[arg(short = 'q', long = "quiet")]
     quiet: bool,
+
+    /// Walk the entire library instead of sampling random songs
+    #[arg(long = "full-library")]
+    full_library: bool,
+
+    /// Number of random songs to sample when not walking the full library
+    #[arg(long = "sample-size", default_value_t = 2000)]
+    sample_size: u32,
+
+    /// Generate from the local library cache, refreshing only changed albums
+    #[arg(long = "use-cache", conflicts_with = "library")]
+    use_cache: bool,
+
+    /// Generate from a library snapshot file instead of fetching songs from the server
+    #[arg(long = "library", value_name = "FILE")]
+    library: Option<PathBuf>,
+
+    /// Save the fetched songs to a library snapshot file for later offline runs
+    #[arg(long = "save-library", value_name = "FILE")]
+    save_library: Option<PathBuf>,
+
+    /// Keep songs exclusive across the playlists of this run
+    #[arg(long = "exclusive")]
+    exclusive: bool,
+
+    /// Write a JSON report of the run, including what changed in each playlist
+    #[arg(long = "report", value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Seed for all random choices; rerunning with the same seed and library
+    /// reproduces the playlists
+    #[arg(long = "seed", value_name = "N")]
+    seed: Option<u64>,
+
+    /// Generate as of this RFC 3339 time instead of now, or the snapshot's save time
+    /// with --library: play recency, playlist names and comment dates use it. Pass a
+    /// report's `reference_time` along with its seed to reproduce the run on a later day
+    #[arg(long = "reference-time", value_name = "TIME")]
+    reference_time: Option<DateTime<Utc>>,
+
+    /// Directory for local state such as the library cache
+    #[arg(long = "data-dir", default_value = "data")]
+    data_dir: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Discard the library cache and rebuild it from the server
+    RebuildCache,
+    /// Show the server version and which optional API features it supports
+    ServerInfo,
+    /// Restore the songs the playlists had before the last run
+    Undo {
+        /// Only undo the latest generation of this playlist config
+        config_name: Option<String>,
+    },
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
+    match &args.command {
+        Some(Command::RebuildCache) => return rebuild_cache(&args),
+        Some(Command::ServerInfo) => return server_info(),
+        Some(Command::Undo { config_name }) => return undo(&args, config_name.as_deref()),
+        None => {}
+    }
+
     // Validate that the playlist configuration file exists before proceeding
     if !std::path::Path::new(&args.config_file).exists() {
         eprintln!(
@@ -48,25 +123,48 @@ fn main() -> Result<()> {
         ));
     }
 
-    // Load configuration from .env
-    let config = load_config()?;
+    // An offline snapshot replaces the server as the song source
+    let snapshot = match &args.library {
+        Some(path) => Some(LibrarySnapshot::load(path)?),
+        None => None,
+    };
 
-    // Initialize API client
-    let client = SubsonicClient::new(config);
+    // The server is only needed to fetch songs or to upload playlists
+    let client = if snapshot.is_some() && args.debug {
+        None
+    } else {
+        Some(SubsonicClient::new(load_config()?))
+    };
+    let sink: Option<&dyn PlaylistSink> = client.as_ref().map(|c| c as &dyn PlaylistSink);
 
-    // Test connection first
-    println!("Testing API connection...");
-    match client.ping() {
-        Ok(_) => println!("✓ API connection successful"),
-        Err(e) => {
-            eprintln!("✗ API connection failed: {e}");
-            return Err(e);
-        }
-    }
+    let songs = if let Some(snapshot) = &snapshot {
+        println!(
+            "\nLoading songs from library snapshot (saved {})...",
+            snapshot.created_at.to_rfc3339()
+        );
+        snapshot.fetch_songs(None)?
+    } else if let Some(client) = &client {
+        fetch_songs_from_server(&args, client)?
+    } else {
+        unreachable!("a client is always created when there is no snapshot")
+    };
 
-    // Fetch random songs from the API
-    println!("\nFetching songs for playlist generation...");
-    let songs = client.fetch_songs(Some(2000))?; // Fetch more songs for better variety (will make multiple API calls if needed)
+    // An offline run generates as of the time its snapshot was saved, so rerunning it
+    // later reproduces the original run
+    let reference_time = match (args.reference_time, &snapshot) {
+        (Some(reference_time), _) => reference_time,
+        (None, Some(snapshot)) => snapshot.created_at,
+        (None, None) => Utc::now(),
+    };
+
+    if let Some(path) = &args.save_library {
+        LibrarySnapshot::new(songs.clone(), reference_time).save(path)?;
+        println!(
+            "Saved {} songs to library snapshot {}",
+            songs.len(),
+            path.display()
+        );
+    }
 
     println!("Fetched {} songs total.", songs.len());
 
@@ -121,7 +219,7 @@ fn main() -> Result<()> {
     let original_count = songs.len();
     let actual_songs: Vec<Song> = songs
         .into_iter()
-        .filter(crate::playlist::filters::SongFilters::is_actual_song)
+        .filter(playlist_generator::playlist::filters::SongFilters::is_actual_song)
         .collect();
 
     let filtered_out_count = original_count - actual_songs.len();
@@ -134,18 +232,23 @@ fn main() -> Result<()> {
     );
 
     // Generate playlists using loaded configurations
-    println!("\nGenerating playlists...");
-    let playlists: Vec<_> = playlist_configs
-        .into_iter()
-        .map(|config| {
-            let generator = PlaylistGenerator::new(config.clone());
-            generator.generate_playlist(
-                actual_songs.clone(),
-                Some(config.name.clone()),
-                config.target_length,
-            )
-        })
-        .collect();
+    let seed = args.seed.unwrap_or_else(rand::random);
+    println!(
+        "\nGenerating playlists (seed {seed}, as of {})...",
+        reference_time.to_rfc3339()
+    );
+    let now = Utc::now();
+    let mut placements = PlacementHistory::load(&args.data_dir)?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let playlists = generate_playlists(
+        &args,
+        &playlist_configs,
+        &actual_songs,
+        sink,
+        &placements,
+        reference_time,
+        &mut rng,
+    );
 
     // Display generation results
     println!("\n=== GENERATION RESULTS ===");
@@ -153,13 +256,39 @@ fn main() -> Result<()> {
 
     // Create playlists via API and log results
     let mut creation_results = Vec::new();
+    let mut skipped_collisions: Vec<(String, String)> = Vec::new();
+    let mut reconciled_duplicates: Vec<ReconciledDuplicate> = Vec::new();
+    let mut history = match sink {
+        Some(_) if !args.debug => Some(RunHistory::load(&args.data_dir)?),
+        _ => None,
+    };
+    let run_at = now.to_rfc3339();
+    let mut report = RunReport::new(
+        run_at.clone(),
+        reference_time.to_rfc3339(),
+        args.debug,
+        seed,
+    );
+    let library: HashMap<&str, &Song> = actual_songs
+        .iter()
+        .map(|song| (song.id.as_str(), song))
+        .collect();
+
+    let mut songs_taken_by_earlier: Vec<(String, usize)> = Vec::new();
 
-    for playlist in &playlists {
+    for generated in &playlists {
+        let playlist = &generated.playlist;
         println!("\n{}", playlist.name);
         println!("{}", "=".repeat(playlist.name.len()));
         println!("Quality Score: {:.1}/100", playlist.quality_score * 100.0);
+        let playlist_report = report.add(playlist);
+        playlist_report.songs_taken_by_earlier = generated.taken_by_earlier;
+        if let Some(taken) = generated.taken_by_earlier {
+            songs_taken_by_earlier.push((playlist.name.clone(), taken));
+        }
 
         if playlist.songs.is_empty() {
+            playlist_report.status = PlaylistStatus::Empty;
             println!("No songs found for this category - skipping playlist creation.");
             creation_results.push((
                 playlist.name.clone(),
@@ -222,6 +351,21 @@ fn main() -> Result<()> {
                 "   Would clean up existing playlists matching pattern: '{}'",
                 playlist.base_name_pattern
             );
+            println!("   Comment: {}", playlist.comment);
+            if let Some(public) = playlist.public {
+                println!("   Visibility: {}", if public { "public" } else { "private" });
+            }
+            if let Some(sink) = sink {
+                match sink.previous_generation(&playlist.base_name_pattern, &playlist.name) {
+                    Ok(Some(previous_ids)) => {
+                        let diff = PlaylistDiff::between(&previous_ids, &song_ids);
+                        print_playlist_diff(&diff, &library);
+                        playlist_report.set_diff(Some(diff));
+                    }
+                    Ok(None) => println!("   Changes: new playlist"),
+                    Err(e) => println!("   Changes: unknown ({})", describe_error(&e)),
+                }
+            }
             
             // Print concise playlist contents with key metrics
             for (i, playlist_song) in playlist.songs.iter().enumerate() {
@@ -258,8 +402,8 @@ fn main() -> Result<()> {
                     .unwrap_or_default();
 
                 let last_played_display = if let Some(played) = &song.played {
-                    use crate::playlist::scoring::PlaylistScoring;
-                    match PlaylistScoring::parse_days_since_played(played) {
+                    use playlist_generator::playlist::scoring::PlaylistScoring;
+                    match PlaylistScoring::parse_days_since_played(played, reference_time) {
                         Ok(days) if days < 1.0 => " (today)".to_string(),
                         Ok(days) if days < 2.0 => " (yesterday)".to_string(),
                         Ok(days) if days < 7.0 => format!(" ({}d ago)", days.round() as u32),
@@ -306,37 +450,106 @@ fn main() -> Result<()> {
                 true,
                 "Debug mode - not uploaded".to_string(),
             ));
-        } else {
-            // Normal mode: Create playlist via API with pattern-based cleanup
+        } else if let Some(sink) = sink {
+            // Normal mode: Create playlist via API, replacing the one this config generated before
             println!("\n🎵 Creating playlist '{}' via API...", playlist.name);
             println!(
-                "   Cleaning up existing playlists matching pattern: '{}'",
+                "   Replacing playlists previously generated from: '{}'",
                 playlist.base_name_pattern
             );
-            match client.create_playlist_with_pattern_cleanup(
-                &playlist.name,
-                &playlist.base_name_pattern,
-                &song_ids,
-            ) {
-                Ok(playlist_id) => {
+            let upload = PlaylistUpload {
+                name: &playlist.name,
+                config_name: &playlist.base_name_pattern,
+                song_ids: &song_ids,
+                comment: &playlist.comment,
+                public: playlist.public,
+                duplicates: playlist.duplicates,
+                keep_history: playlist.keep_history,
+            };
+            match sink.create_playlist_with_pattern_cleanup(&upload) {
+                Ok(outcome) => {
                     println!(
                         "✓ Successfully created playlist '{}' with ID: {}",
-                        playlist.name, playlist_id
+                        playlist.name, outcome.playlist_id
                     );
+                    match &outcome.diff {
+                        Some(diff) => println!("   Changes: {diff}"),
+                        None => println!("   Changes: new playlist"),
+                    }
+                    playlist_report.status = PlaylistStatus::Published;
+                    playlist_report.playlist_id = Some(outcome.playlist_id.clone());
+                    playlist_report.set_diff(outcome.diff);
+                    let action = if outcome.updated_existing {
+                        "Updated"
+                    } else {
+                        "Created"
+                    };
                     creation_results.push((
                         playlist.name.clone(),
                         true,
-                        format!("Created with ID: {playlist_id}"),
+                        format!("{action} with ID: {}", outcome.playlist_id),
                     ));
+                    skipped_collisions.extend(
+                        outcome
+                            .collisions
+                            .into_iter()
+                            .map(|name| (name, playlist.base_name_pattern.clone())),
+                    );
+                    reconciled_duplicates.extend(outcome.duplicates);
+                    if !outcome.pruned_archives.is_empty() {
+                        println!(
+                            "   Removed {} archived generations beyond the history limit",
+                            outcome.pruned_archives.len()
+                        );
+                    }
+                    placements.record(&playlist.base_name_pattern, &song_ids, now);
+                    if let Some(history) = &mut history {
+                        history.record(Generation {
+                            run_at: run_at.clone(),
+                            config_name: playlist.base_name_pattern.clone(),
+                            playlist_id: outcome.playlist_id,
+                            playlist_name: playlist.name.clone(),
+                            song_ids,
+                        });
+                    }
                 }
                 Err(e) => {
-                    eprintln!("✗ Failed to create playlist '{}': {}", playlist.name, e);
-                    creation_results.push((playlist.name.clone(), false, format!("Error: {e}")));
+                    let message = describe_error(&e);
+                    eprintln!("✗ Failed to create playlist '{}': {}", playlist.name, message);
+                    playlist_report.status = PlaylistStatus::Failed;
+                    playlist_report.error = Some(message.clone());
+                    creation_results.push((playlist.name.clone(), false, format!("Error: {message}")));
                 }
             }
         }
     }
 
+    // Measured after publishing, so only songs that reached the server count. Every
+    // config yields one generated playlist, so the configs line up with the report.
+    let days_since_surfaced = placements.days_since_surfaced(now);
+    println!("\n=== COVERAGE (published playlists only) ===");
+    for (config, playlist_report) in playlist_configs.iter().zip(&mut report.playlists) {
+        let coverage = measure_coverage(config, &actual_songs, &days_since_surfaced);
+        println!("{}: {coverage}", config.name);
+        playlist_report.coverage = Some(coverage);
+    }
+
+    if let Some(history) = &history
+        && let Err(e) = history.save(&args.data_dir)
+    {
+        eprintln!("⚠️ Could not save run history, undo will not cover this run: {e:#}");
+    }
+    if history.is_some()
+        && let Err(e) = placements.save(&args.data_dir)
+    {
+        eprintln!("⚠️ Could not save placement history: {e:#}");
+    }
+
+    if let Some(path) = &args.report {
+        report.save(path)?;
+        println!("Run report written to {}", path.display());
+    }
+
     // Summary of playlist creation results (suitable for cron job monitoring)
     println!("\n=== PLAYLIST CREATION SUMMARY ===");
     let successful_creations = creation_results
@@ -352,6 +565,27 @@ fn main() -> Result<()> {
         println!("{status} {name}: {message}");
     }
 
+    if !skipped_collisions.is_empty() {
+        println!("\nLeft alone (similar name, not created by playlist-generator):");
+        for (name, config_name) in &skipped_collisions {
+            println!("  - '{name}' (matches config '{config_name}')");
+        }
+    }
+
+    if !songs_taken_by_earlier.is_empty() {
+        println!("\nMatching songs already used by earlier playlists (--exclusive):");
+        for (name, taken) in &songs_taken_by_earlier {
+            println!("  - {name}: {taken}");
+        }
+    }
+
+    if !reconciled_duplicates.is_empty() {
+        println!("\nDuplicate generated playlists:");
+        for duplicate in &reconciled_duplicates {
+            println!("  - '{}' {}", duplicate.name, duplicate.action);
+        }
+    }
+
     if successful_creations == total_attempts && total_attempts > 0 {
         println!("\n🎉 All playlists created successfully! Daily playlist generation complete.");
     } else if successful_creations > 0 {
@@ -365,3 +599,349 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Check the server connection and fetch songs from it, either as a random
+/// sample, a full library walk, or through the local library cache
+fn fetch_songs_from_server(args: &Args, client: &SubsonicClient) -> Result<Vec<Song>> {
+    let source: &dyn MusicSource = client;
+
+    // Test connection first
+    println!("Testing API connection...");
+    let server_reachable = match source.ping() {
+        Ok(_) => {
+            let capabilities = client.capabilities();
+            println!(
+                "✓ API connection successful ({} {}, API {})",
+                capabilities.server_type.as_deref().unwrap_or("Subsonic"),
+                capabilities.server_version.as_deref().unwrap_or(""),
+                capabilities.api_version
+            );
+            if !capabilities.supports_multiple_genres() && !args.quiet {
+                println!("  Server reports one genre per song; genre filters use that genre only");
+            }
+            true
+        }
+        Err(e) if args.use_cache => {
+            eprintln!("✗ API connection failed: {}", describe_error(&e));
+            false
+        }
+        Err(e) => {
+            eprintln!("✗ API connection failed: {}", describe_error(&e));
+            return Err(e);
+        }
+    };
+
+    println!("\nFetching songs for playlist generation...");
+    if args.use_cache {
+        load_songs_from_cache(args, client, server_reachable)
+    } else if args.full_library {
+        println!("Walking the full library (this may take a while)...");
+        source.fetch_all_songs(&mut |progress| print_library_progress(args, progress))
+    } else {
+        source.fetch_songs(Some(args.sample_size)) // Will make multiple API calls if needed
+    }
+}
+
+/// Print periodic progress while walking the library album by album
+fn print_library_progress(args: &Args, progress: &LibraryProgress) {
+    let finished = progress.albums_scanned == progress.albums_total;
+    if !args.quiet && (progress.albums_scanned.is_multiple_of(100) || finished) {
+        println!(
+            "  Scanned {}/{} albums ({} songs)",
+            progress.albums_scanned, progress.albums_total, progress.songs_found
+        );
+    }
+}
+
+/// Refresh the library cache from the server and return its songs, falling back
+/// to the cached copy when the server is unreachable or the refresh fails
+fn load_songs_from_cache(
+    args: &Args,
+    client: &SubsonicClient,
+    server_reachable: bool,
+) -> Result<Vec<Song>> {
+    let cached = LibraryCache::load(&args.data_dir)?;
+
+    if !server_reachable {
+        return match cached {
+            Some(cache) => {
+                println!(
+                    "Server unreachable - using library cache from {} ({} songs)",
+                    cache.synced_at.as_deref().unwrap_or("unknown time"),
+                    cache.song_count()
+                );
+                Ok(cache.songs())
+            }
+            None => Err(anyhow::anyhow!(
+                "Server unreachable and no library cache found in {}",
+                args.data_dir.display()
+            )),
+        };
+    }
+
+    let had_cache = cached.is_some();
+    let mut cache = cached.unwrap_or_else(LibraryCache::new);
+    println!("Refreshing library cache in {}...", args.data_dir.display());
+
+    match cache.refresh(client, |progress| print_library_progress(args, progress)) {
+        Ok(stats) => {
+            println!(
+                "✓ Library cache refreshed: {} albums unchanged, {} refreshed, {} removed",
+                stats.unchanged, stats.refreshed, stats.removed
+            );
+            cache.save(&args.data_dir)?;
+            Ok(cache.songs())
+        }
+        Err(e) if had_cache => {
+            eprintln!(
+                "✗ Library cache refresh failed, using cached songs: {}",
+                describe_error(&e)
+            );
+            Ok(cache.songs())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Throw away the library cache and rebuild it from a full library walk
+fn rebuild_cache(args: &Args) -> Result<()> {
+    let client = SubsonicClient::new(load_config()?);
+    client.ping()?;
+
+    println!("Rebuilding library cache in {}...", args.data_dir.display());
+    let mut cache = LibraryCache::new();
+    let stats = cache.refresh(&client, |progress| print_library_progress(args, progress))?;
+    cache.save(&args.data_dir)?;
+
+    println!(
+        "✓ Library cache rebuilt: {} albums, {} songs",
+        stats.refreshed,
+        cache.song_count()
+    );
+    Ok(())
+}
+
+/// Put back the previous songs of every playlist published by the last run, or of
+/// one config's playlist, using the local run history
+fn undo(args: &Args, config_name: Option<&str>) -> Result<()> {
+    let mut history = RunHistory::load(&args.data_dir)?;
+    let configs = match config_name {
+        Some(name) => vec![name.to_string()],
+        None => history.last_run_configs(),
+    };
+    if configs.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No generated playlists recorded in {}",
+            RunHistory::path(&args.data_dir).display()
+        ));
+    }
+
+    let client = SubsonicClient::new(load_config()?);
+    let playlists = client.get_playlists()?;
+    let mut failures = 0;
+
+    for config in &configs {
+        let Some((latest, previous)) = history.last_two(config) else {
+            println!("✗ {config}: no earlier generation to go back to");
+            failures += 1;
+            continue;
+        };
+        let Some(current) = playlists.iter().find(|p| p.id == latest.playlist_id) else {
+            println!(
+                "✗ {config}: playlist '{}' no longer exists on the server",
+                latest.playlist_name
+            );
+            failures += 1;
+            continue;
+        };
+
+        let previous = previous.clone();
+        match client.replace_playlist(current, &previous.playlist_name, &previous.song_ids) {
+            Ok(_) => {
+                println!(
+                    "✓ {config}: restored '{}' with {} songs from {}",
+                    previous.playlist_name,
+                    previous.song_ids.len(),
+                    previous.run_at
+                );
+                history.remove_latest(config);
+            }
+            Err(e) => {
+                println!("✗ {config}: {}", describe_error(&e));
+                failures += 1;
+            }
+        }
+    }
+
+    history.save(&args.data_dir)?;
+    if failures > 0 {
+        return Err(anyhow::anyhow!("{failures} playlist(s) could not be restored"));
+    }
+    Ok(())
+}
+
+/// Print what the server reports about itself and the features it supports
+fn server_info() -> Result<()> {
+    let client = SubsonicClient::new(load_config()?);
+    let capabilities = client.ping()?;
+    println!("{capabilities}");
+    Ok(())
+}
+
+/// A generated playlist with the statistics reported alongside it
+struct GeneratedPlaylist {
+    playlist: Playlist,
+    /// With `--exclusive`: matching songs that earlier playlists had already taken
+    taken_by_earlier: Option<usize>,
+}
+
+/// Generate a playlist per config as of `now`, in config file order. With `--exclusive`,
+/// configs pick songs in priority order and each song goes to at most one playlist,
+/// except for playlists that opt out with `allow_shared_songs`.
+fn generate_playlists(
+    args: &Args,
+    configs: &[PlaylistConfig],
+    songs: &[Song],
+    sink: Option<&dyn PlaylistSink>,
+    placements: &PlacementHistory,
+    now: DateTime<Utc>,
+    rng: &mut StdRng,
+) -> Vec<GeneratedPlaylist> {
+    let mut order: Vec<usize> = (0..configs.len()).collect();
+    if args.exclusive {
+        order.sort_by_key(|&index| std::cmp::Reverse(configs[index].priority));
+    }
+
+    let days_since_surfaced = placements.days_since_surfaced(now);
+    let mut claimed: HashSet<String> = HashSet::new();
+    let mut generated: Vec<Option<(Playlist, Option<usize>)>> =
+        configs.iter().map(|_| None).collect();
+
+    for index in order {
+        let config = &configs[index];
+        let exclusive = args.exclusive && !config.allow_shared_songs;
+        let is_free = |song: &Song| !exclusive || !claimed.contains(&song.id);
+
+        let available: Vec<Song> = songs.iter().filter(|s| is_free(s)).cloned().collect();
+        let taken_by_earlier = exclusive.then(|| {
+            songs
+                .iter()
+                .filter(|s| !is_free(s) && SongFilters::should_include_song(s, config))
+                .count()
+        });
+
+        let mut generator = PlaylistGenerator::new(config.clone())
+            .with_rng(StdRng::seed_from_u64(rng.next_u64()))
+            .with_reference_time(now)
+            .with_days_since_generated(placements.days_since_placed(&config.name, now));
+        if config.rotation.is_some() {
+            generator = generator.with_days_since_surfaced(days_since_surfaced.clone());
+        }
+        let previous = match (&config.refresh, sink) {
+            (Some(_), Some(sink)) => load_songs_to_refresh(sink, &config.name, songs),
+            _ => None,
+        };
+        let playlist = match previous {
+            Some(previous) => {
+                let previous: Vec<Song> = previous.into_iter().filter(is_free).collect();
+                generator.refresh_playlist(
+                    available,
+                    &previous,
+                    Some(config.name.clone()),
+                    config.target_length,
+                )
+            }
+            None => generator.generate_playlist(
+                available,
+                Some(config.name.clone()),
+                config.target_length,
+            ),
+        };
+
+        if exclusive {
+            claimed.extend(playlist.songs.iter().map(|ps| ps.song.id.clone()));
+        }
+        generated[index] = Some((playlist, taken_by_earlier));
+    }
+
+    generated
+        .into_iter()
+        .filter_map(|generated| {
+            let (playlist, taken_by_earlier) = generated?;
+            Some(GeneratedPlaylist {
+                playlist,
+                taken_by_earlier,
+            })
+        })
+        .collect()
+}
+
+/// Share of a config's eligible songs that any generated playlist published within
+/// the config's rotation period, this run's uploads included
+fn measure_coverage(
+    config: &PlaylistConfig,
+    songs: &[Song],
+    days_since_surfaced: &HashMap<String, f32>,
+) -> Coverage {
+    let period_days = config
+        .rotation
+        .as_ref()
+        .map_or(RotationSettings::DEFAULT_PERIOD_DAYS, |r| r.period_days);
+    let eligible = songs
+        .iter()
+        .filter(|s| SongFilters::should_include_song(s, config))
+        .map(|s| s.id.as_str());
+    Coverage::measure(eligible, days_since_surfaced, period_days)
+}
+
+/// The current songs of a config's playlist for a partial refresh, preferring the
+/// library's copy of each song for up-to-date play counts. `None` means generating
+/// from scratch, either because there is no playlist yet or it could not be read.
+fn load_songs_to_refresh(
+    sink: &dyn PlaylistSink,
+    config_name: &str,
+    library: &[Song],
+) -> Option<Vec<Song>> {
+    match sink.previous_generation_songs(config_name) {
+        Ok(Some(songs)) if !songs.is_empty() => Some(
+            songs
+                .into_iter()
+                .map(|song| match library.iter().find(|s| s.id == song.id) {
+                    Some(fresh) => fresh.clone(),
+                    None => song,
+                })
+                .collect(),
+        ),
+        Ok(_) => None,
+        Err(e) => {
+            eprintln!(
+                "⚠️ Could not load the current '{config_name}' playlist, generating it from scratch: {}",
+                describe_error(&e)
+            );
+            None
+        }
+    }
+}
+
+/// Print how a playlist would change, naming the songs that come and go
+fn print_playlist_diff(diff: &PlaylistDiff, library: &HashMap<&str, &Song>) {
+    println!("   Changes vs. current playlist: {diff}");
+    let describe = |id: &String| match library.get(id.as_str()) {
+        Some(song) => format!("\"{}\" by {}", song.title, song.artist),
+        None => format!("song {id}"),
+    };
+    for id in &diff.added {
+        println!("     + {}", describe(id));
+    }
+    for id in &diff.removed {
+        println!("     - {}", describe(id));
+    }
+}
+
+/// Render an error with its full context chain, plus a hint for known Subsonic errors
+fn describe_error(e: &anyhow::Error) -> String {
+    match e.downcast_ref::<SubsonicError>().and_then(SubsonicError::hint) {
+        Some(hint) => format!("{e:#} ({hint})"),
+        None => format!("{e:#}"),
+    }
+}
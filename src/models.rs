@@ -35,30 +35,64 @@ pub struct Genre {
     pub name: String,
 }
 
-/// Response structure for getRandomSongs API call
+/// Envelope wrapped around every Subsonic API response
 #[derive(Debug, Deserialize)]
-pub struct RandomSongsResponse {
+pub struct SubsonicEnvelope<T> {
     #[serde(rename = "subsonic-response")]
-    pub subsonic_response: SubsonicResponse,
+    pub subsonic_response: SubsonicResponse<T>,
 }
 
+/// Fields common to every Subsonic response, plus the endpoint-specific payload
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct SubsonicResponse {
+pub struct SubsonicResponse<T> {
     pub status: String,
     /// allow undefined version for flexibility
+    #[serde(default)]
     pub version: String,
+    pub error: Option<ApiError>,
+    #[serde(flatten)]
+    pub payload: T,
+}
+
+/// Error object included when a response has status "failed"
+#[derive(Debug, Deserialize)]
+pub struct ApiError {
+    pub code: u32,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Payload for endpoints that only return a status (ping, updatePlaylist, deletePlaylist)
+#[derive(Debug, Deserialize)]
+pub struct EmptyPayload {}
+
+/// Server details included in a ping response by OpenSubsonic servers
+#[derive(Debug, Deserialize)]
+pub struct PingPayload {
+    #[serde(rename = "type")]
+    pub server_type: Option<String>,
+    #[serde(rename = "serverVersion")]
+    pub server_version: Option<String>,
+    #[serde(rename = "openSubsonic", default)]
+    pub open_subsonic: bool,
+}
+
+/// Payload of the getRandomSongs API call
+#[derive(Debug, Deserialize)]
+pub struct RandomSongsPayload {
     #[serde(rename = "randomSongs")]
     pub random_songs: Option<RandomSongs>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RandomSongs {
+    #[serde(default)]
     pub song: Vec<Song>,
 }
 
 impl Song {
-    /// Get all genres for this song, combining both single genre and genres array
+    /// Get all genres for this song, combining both single genre and genres array. The
+    /// client clears the array for servers without multi-genre support.
     pub fn get_all_genres(&self) -> Vec<String> {
         let mut all_genres = Vec::new();
 
@@ -117,18 +151,84 @@ impl Default for Song {
     }
 }
 
-/// Response structure for createPlaylist API call
+/// Payload of the getAlbumList2 API call
 #[derive(Debug, Deserialize)]
-pub struct CreatePlaylistResponse {
-    #[serde(rename = "subsonic-response")]
-    pub subsonic_response: CreatePlaylistSubsonicResponse,
+pub struct AlbumListPayload {
+    #[serde(rename = "albumList2")]
+    pub album_list: Option<AlbumList>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct CreatePlaylistSubsonicResponse {
-    pub status: String,
+pub struct AlbumList {
+    #[serde(default)]
+    pub album: Vec<AlbumInfo>,
+}
+
+/// Album summary as returned by getAlbumList2 (ID3 tags)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlbumInfo {
+    pub id: String,
+    pub name: String,
+    pub artist: Option<String>,
+    #[serde(rename = "songCount")]
+    pub song_count: Option<u32>,
+    pub created: Option<String>,
+    pub changed: Option<String>, // OpenSubsonic extension, not sent by every server
+    #[serde(rename = "playCount")]
+    pub play_count: Option<u32>,
+    pub played: Option<String>,
+    pub starred: Option<String>,
+}
+
+impl AlbumInfo {
+    /// Fingerprint used to detect whether an album needs to be re-fetched.
+    /// Includes user data so play counts and stars on cached songs stay fresh. The
+    /// album summary carries no per-song stars, so the IDs of the album's starred
+    /// songs (from getStarred2) are passed in, sorted.
+    pub fn fingerprint(&self, starred_song_ids: &[&str]) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}",
+            self.changed.as_deref().unwrap_or_default(),
+            self.created.as_deref().unwrap_or_default(),
+            self.song_count.unwrap_or(0),
+            self.play_count.unwrap_or(0),
+            self.played.as_deref().unwrap_or_default(),
+            self.starred.as_deref().unwrap_or_default(),
+            starred_song_ids.join(",")
+        )
+    }
+}
+
+/// Payload of the getStarred2 API call
+#[derive(Debug, Deserialize)]
+pub struct StarredPayload {
+    #[serde(rename = "starred2")]
+    pub starred: Option<StarredList>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StarredList {
+    #[serde(default)]
+    pub song: Vec<Song>,
+}
+
+/// Payload of the getAlbum API call
+#[derive(Debug, Deserialize)]
+pub struct AlbumPayload {
+    pub album: Option<AlbumWithSongs>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumWithSongs {
     #[allow(dead_code)]
-    pub version: String,
+    pub id: String,
+    #[serde(default)]
+    pub song: Vec<Song>,
+}
+
+/// Payload of the createPlaylist API call
+#[derive(Debug, Deserialize)]
+pub struct CreatePlaylistPayload {
     pub playlist: Option<CreatedPlaylist>,
 }
 
@@ -150,23 +250,29 @@ pub struct CreatedPlaylist {
     pub changed: Option<String>,
 }
 
-/// Response structure for getPlaylists API call
-#[derive(Debug, Deserialize)]
-pub struct GetPlaylistsResponse {
-    #[serde(rename = "subsonic-response")]
-    pub subsonic_response: GetPlaylistsSubsonicResponse,
+impl From<PlaylistInfo> for CreatedPlaylist {
+    fn from(info: PlaylistInfo) -> Self {
+        Self {
+            id: info.id,
+            name: info.name,
+            song_count: info.song_count,
+            duration: info.duration,
+            public: info.public,
+            created: info.created,
+            changed: info.changed,
+        }
+    }
 }
 
+/// Payload of the getPlaylists API call
 #[derive(Debug, Deserialize)]
-pub struct GetPlaylistsSubsonicResponse {
-    pub status: String,
-    #[allow(dead_code)]
-    pub version: String,
+pub struct PlaylistsPayload {
     pub playlists: Option<PlaylistsContainer>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PlaylistsContainer {
+    #[serde(default)]
     pub playlist: Vec<PlaylistInfo>,
 }
 
@@ -185,4 +291,82 @@ pub struct PlaylistInfo {
     pub created: Option<String>,
     #[allow(dead_code)]
     pub changed: Option<String>,
+    pub comment: Option<String>,
+    pub owner: Option<String>,
+}
+
+impl PlaylistInfo {
+    /// Whether the playlist carries the ownership marker for the given config
+    pub fn is_owned_by(&self, config_name: &str) -> bool {
+        self.comment
+            .as_deref()
+            .and_then(crate::ownership::owner_config)
+            == Some(config_name)
+    }
+
+    /// Whether the playlist carries an ownership or archive marker for any config
+    pub fn is_generated(&self) -> bool {
+        self.comment.as_deref().is_some_and(|comment| {
+            crate::ownership::owner_config(comment).is_some()
+                || crate::ownership::archived_config(comment).is_some()
+        })
+    }
+
+    /// Whether the playlist was generated from the given config and has since been archived
+    pub fn is_archived_from(&self, config_name: &str) -> bool {
+        self.comment
+            .as_deref()
+            .and_then(crate::ownership::archived_config)
+            == Some(config_name)
+    }
+}
+
+/// Payload of the getPlaylist API call
+#[derive(Debug, Deserialize)]
+pub struct PlaylistPayload {
+    pub playlist: Option<PlaylistWithEntries>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistWithEntries {
+    #[allow(dead_code)]
+    pub id: String,
+    #[serde(default)]
+    pub entry: Vec<PlaylistEntry>,
+}
+
+/// Payload of the getPlaylist API call, keeping each entry's raw fields so they can be
+/// read as full songs
+#[derive(Debug, Deserialize)]
+pub struct PlaylistSongsPayload {
+    pub playlist: Option<PlaylistWithSongs>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PlaylistWithSongs {
+    #[serde(default)]
+    pub entry: Vec<serde_json::Value>,
+}
+
+/// A track inside a playlist. Only the ID is required so odd entries never break parsing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaylistEntry {
+    pub id: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+}
+
+/// Payload of the getOpenSubsonicExtensions API call
+#[derive(Debug, Deserialize)]
+pub struct ExtensionsPayload {
+    #[serde(rename = "openSubsonicExtensions", default)]
+    pub extensions: Vec<OpenSubsonicExtension>,
+}
+
+/// An OpenSubsonic extension and the versions of it the server implements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenSubsonicExtension {
+    pub name: String,
+    #[serde(default)]
+    pub versions: Vec<u32>,
 }
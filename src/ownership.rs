@@ -0,0 +1,67 @@
+//! Ownership markers for generated playlists.
+//!
+//! Every playlist the tool writes carries a tag in its comment naming the config that
+//! produced it. Cleanup and updates only ever touch playlists carrying the tag for their
+//! config, so hand-made playlists with similar names are left alone. Archived playlists
+//! carry a separate tag, so they are recognised but no longer managed.
+
+const MARKER_PREFIX: &str = "[playlist-generator: ";
+const ARCHIVE_MARKER_PREFIX: &str = "[playlist-generator archive: ";
+const MARKER_SUFFIX: &str = "]";
+
+/// The marker identifying playlists generated from `config_name`
+pub fn marker(config_name: &str) -> String {
+    format!("{MARKER_PREFIX}{config_name}{MARKER_SUFFIX}")
+}
+
+/// The marker identifying archived playlists that were generated from `config_name`
+pub fn archive_marker(config_name: &str) -> String {
+    format!("{ARCHIVE_MARKER_PREFIX}{config_name}{MARKER_SUFFIX}")
+}
+
+/// Append the ownership marker to a comment, replacing any marker already present
+pub fn mark_comment(comment: &str, config_name: &str) -> String {
+    with_marker(comment, &marker(config_name))
+}
+
+/// Swap a comment's marker for the archive marker
+pub fn mark_archived(comment: &str, config_name: &str) -> String {
+    with_marker(comment, &archive_marker(config_name))
+}
+
+/// The config name recorded in a comment's ownership marker, if it has one
+pub fn owner_config(comment: &str) -> Option<&str> {
+    tagged_config(comment, MARKER_PREFIX)
+}
+
+/// The config name recorded in a comment's archive marker, if it has one
+pub fn archived_config(comment: &str) -> Option<&str> {
+    tagged_config(comment, ARCHIVE_MARKER_PREFIX)
+}
+
+/// The comment without its ownership or archive marker
+pub fn strip_marker(comment: &str) -> &str {
+    [MARKER_PREFIX, ARCHIVE_MARKER_PREFIX]
+        .into_iter()
+        .filter(|prefix| tagged_config(comment, prefix).is_some())
+        .filter_map(|prefix| comment.rfind(prefix))
+        .min()
+        .map_or(comment, |start| &comment[..start])
+}
+
+fn with_marker(comment: &str, marker: &str) -> String {
+    let comment = strip_marker(comment).trim_end();
+    if comment.is_empty() {
+        marker.to_string()
+    } else {
+        format!("{comment}\n{marker}")
+    }
+}
+
+/// The marker is always the end of the comment, so the name runs to the last `]` and
+/// may itself contain brackets, e.g. `Mix [Live]`
+fn tagged_config<'a>(comment: &'a str, prefix: &str) -> Option<&'a str> {
+    let start = comment.rfind(prefix)? + prefix.len();
+    let end = start + comment[start..].rfind(MARKER_SUFFIX)?;
+    Some(&comment[start..end])
+}
@@ -0,0 +1,181 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Bump this whenever the on-disk layout changes; older files are discarded
+pub const PLACEMENT_HISTORY_VERSION: u32 = 1;
+
+const PLACEMENT_HISTORY_FILE: &str = "placements.json";
+
+/// Placements older than this are forgotten to keep the file small
+const RETENTION_DAYS: i64 = 365;
+
+/// When each song was last placed in each config's published playlist. Unlike play
+/// counts this also covers songs that were offered but never played.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlacementHistory {
+    pub version: u32,
+    /// Config name -> song ID -> time of the last run that published it (RFC 3339)
+    pub configs: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl PlacementHistory {
+    /// Create an empty history with the current version
+    pub fn new() -> Self {
+        Self {
+            version: PLACEMENT_HISTORY_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Location of the history file inside the data directory
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(PLACEMENT_HISTORY_FILE)
+    }
+
+    /// Load the history from disk, starting a new one if there is none yet or it
+    /// was written by an incompatible version
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = Self::path(data_dir);
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let history: PlacementHistory = serde_json::from_str(&content).map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to parse placement history {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+
+        if history.version != PLACEMENT_HISTORY_VERSION {
+            println!(
+                "Placement history version {} is outdated (expected {}), starting a new one",
+                history.version, PLACEMENT_HISTORY_VERSION
+            );
+            return Ok(Self::new());
+        }
+
+        Ok(history)
+    }
+
+    /// Write the history to disk, replacing the previous file atomically
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = Self::path(data_dir);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(self)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Note that the songs were published in the config's playlist at `at`, and forget
+    /// placements past the retention period
+    pub fn record(&mut self, config_name: &str, song_ids: &[String], at: DateTime<Utc>) {
+        let placements = self.configs.entry(config_name.to_string()).or_default();
+        let timestamp = at.to_rfc3339();
+        for id in song_ids {
+            placements.insert(id.clone(), timestamp.clone());
+        }
+
+        let cutoff = at - chrono::Duration::days(RETENTION_DAYS);
+        placements.retain(|_, placed_at| {
+            DateTime::parse_from_rfc3339(placed_at).is_ok_and(|placed_at| placed_at >= cutoff)
+        });
+    }
+
+    /// Days since each song was last placed in the config's playlist, as of `now`
+    pub fn days_since_placed(&self, config_name: &str, now: DateTime<Utc>) -> HashMap<String, f32> {
+        let Some(placements) = self.configs.get(config_name) else {
+            return HashMap::new();
+        };
+        placements
+            .iter()
+            .filter_map(|(id, placed_at)| Some((id.clone(), days_since(placed_at, now)?)))
+            .collect()
+    }
+
+    /// Days since each song was last placed in any config's playlist, as of `now`
+    pub fn days_since_surfaced(&self, now: DateTime<Utc>) -> HashMap<String, f32> {
+        let mut surfaced: HashMap<String, f32> = HashMap::new();
+        for (id, placed_at) in self.configs.values().flatten() {
+            let Some(days) = days_since(placed_at, now) else {
+                continue;
+            };
+            surfaced
+                .entry(id.clone())
+                .and_modify(|latest| *latest = latest.min(days))
+                .or_insert(days);
+        }
+        surfaced
+    }
+}
+
+fn days_since(placed_at: &str, now: DateTime<Utc>) -> Option<f32> {
+    let placed_at = DateTime::parse_from_rfc3339(placed_at).ok()?;
+    let hours = now.signed_duration_since(placed_at).num_hours();
+    Some((hours as f32 / 24.0).max(0.0))
+}
+
+/// How much of a config's eligible songs the generated playlists have surfaced lately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Coverage {
+    /// Songs in the library matching the config's filters
+    pub eligible: usize,
+    /// Eligible songs any generated playlist published within the period
+    pub surfaced: usize,
+    pub period_days: u32,
+}
+
+impl Coverage {
+    /// Count the eligible songs surfaced within `period_days` according to
+    /// `days_since_surfaced`
+    pub fn measure<'a>(
+        eligible: impl IntoIterator<Item = &'a str>,
+        days_since_surfaced: &HashMap<String, f32>,
+        period_days: u32,
+    ) -> Self {
+        let mut coverage = Self {
+            eligible: 0,
+            surfaced: 0,
+            period_days,
+        };
+        for id in eligible {
+            coverage.eligible += 1;
+            let recent = days_since_surfaced
+                .get(id)
+                .is_some_and(|&days| days < period_days as f32);
+            if recent {
+                coverage.surfaced += 1;
+            }
+        }
+        coverage
+    }
+
+    /// Share of the eligible songs that were surfaced, from 0 to 1
+    pub fn share(&self) -> f32 {
+        if self.eligible == 0 {
+            0.0
+        } else {
+            self.surfaced as f32 / self.eligible as f32
+        }
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} eligible songs surfaced in the last {} days ({:.0}%)",
+            self.surfaced,
+            self.eligible,
+            self.period_days,
+            self.share() * 100.0
+        )
+    }
+}
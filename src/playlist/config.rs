@@ -14,6 +14,108 @@ pub struct PlaylistConfig {
     pub preference_weights: PreferenceWeights,
     pub target_length: Option<usize>, // Default target length for this playlist type
     pub min_days_since_last_play: Option<u32>, // Minimum days since last play for a song to be included
+    #[serde(default)]
+    pub public: Option<bool>, // Playlist visibility on the server; None leaves it unchanged
+    #[serde(default)]
+    pub comment: Option<String>, // Comment template, see `PlaylistComment`; None uses the default summary
+    #[serde(default)]
+    pub duplicates: DuplicatePolicy, // What to do with extra playlists generated from this config
+    #[serde(default)]
+    pub keep_history: Option<usize>, // Archive previous generations instead of replacing them, keeping this many
+    #[serde(default)]
+    pub refresh: Option<RefreshSettings>, // Evolve the existing playlist instead of regenerating it
+    #[serde(default)]
+    pub priority: i32, // With --exclusive, higher priorities pick their songs first; ties keep file order
+    #[serde(default)]
+    pub allow_shared_songs: bool, // Opt out of --exclusive: may reuse songs other playlists picked
+    #[serde(default)]
+    pub min_days_since_last_generated: Option<u32>, // Minimum days since a song was last published in this playlist
+    #[serde(default)]
+    pub recently_generated_penalty: Option<f32>, // Turns min_days_since_last_generated into a score penalty of up to this much
+    #[serde(default)]
+    pub rotation: Option<RotationSettings>, // Favour songs no generated playlist has surfaced lately
+    #[serde(default)]
+    pub selection: Option<SelectionSettings>, // Sample among good candidates instead of always taking the best
+    #[serde(default)]
+    pub optimize: Option<OptimizationSettings>, // Improve the greedy result with a second, global pass
+}
+
+/// Second pass after greedy generation: local search over swaps, segment reversals
+/// and replacements, keeping the same hard constraints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizationSettings {
+    #[serde(default = "OptimizationSettings::default_time_budget_ms")]
+    pub time_budget_ms: u64, // Stop searching after this long, keeping the best order found
+    #[serde(default = "OptimizationSettings::default_replacement_candidates")]
+    pub replacement_candidates: usize, // Unused candidates (best preference first) tried as replacements; 0 only reorders
+}
+
+impl OptimizationSettings {
+    fn default_time_budget_ms() -> u64 {
+        500
+    }
+
+    fn default_replacement_candidates() -> usize {
+        50
+    }
+}
+
+impl Default for OptimizationSettings {
+    fn default() -> Self {
+        Self {
+            time_budget_ms: Self::default_time_budget_ms(),
+            replacement_candidates: Self::default_replacement_candidates(),
+        }
+    }
+}
+
+/// Stochastic selection: each next song is drawn from the candidates with a softmax
+/// over their combined scores instead of always taking the highest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionSettings {
+    pub temperature: f32, // 0 = always the best fit; higher values give weaker candidates more chances
+    #[serde(default)]
+    pub top_k: Option<usize>, // Only draw from this many best candidates
+    #[serde(default)]
+    pub top_p: Option<f32>, // Only draw from the best candidates holding this share of the probability
+}
+
+/// Rotation: a preference bonus for songs that no generated playlist has surfaced
+/// recently, so every song matching the config gets a turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RotationSettings {
+    pub weight: f32, // Bonus for songs not surfaced within the period; less for more recent ones
+    #[serde(default = "RotationSettings::default_period_days")]
+    pub period_days: u32, // How long a song counts as recently surfaced
+}
+
+impl RotationSettings {
+    /// Period used for rotation and for the coverage report when none is configured
+    pub const DEFAULT_PERIOD_DAYS: u32 = 28;
+
+    fn default_period_days() -> u32 {
+        Self::DEFAULT_PERIOD_DAYS
+    }
+}
+
+/// Partial refresh: keep the best songs of the current playlist and replace the rest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RefreshSettings {
+    pub replace_fraction: f32, // Share of the playlist replaced per run (0.0 to 1.0)
+}
+
+/// What to do with extra playlists generated from the same config, e.g. left over
+/// from older runs or from a rename. One canonical playlist is always kept and updated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicatePolicy {
+    /// Delete the extra playlists
+    #[default]
+    Delete,
+    /// Rename them with an "(archived <date>)" suffix and stop managing them
+    Archive,
+    /// Leave them in place and only report them
+    Keep,
 }
 
 /// BPM range for playlist filtering
@@ -90,31 +192,6 @@ pub struct PreferenceWeights {
     pub play_count_filter: Option<PlayCountFilter>, // Filter songs by play count
 }
 
-/// Collection of playlist configurations loaded from JSON
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PlaylistConfigs {
-    pub playlists: Vec<PlaylistConfig>,
-}
-
-/// Settings for iterative playlist generation with quality evaluation
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IterativeSettings {
-    pub min_quality_threshold: f32, // Minimum quality score to accept a song
-    pub max_attempts_per_position: usize, // Maximum attempts before settling for best candidate
-    #[serde(default)]
-    pub debug_output: bool, // Enable debug output for artist repetition checking
-}
-
-impl Default for IterativeSettings {
-    fn default() -> Self {
-        Self {
-            min_quality_threshold: 0.3,
-            max_attempts_per_position: 10,
-            debug_output: true,
-        }
-    }
-}
-
 impl Default for PlaylistConfig {
     fn default() -> Self {
         Self {
@@ -146,6 +223,18 @@ impl Default for PlaylistConfig {
             },
             target_length: Some(20),
             min_days_since_last_play: None, // Default to no minimum day restriction
+            public: None,
+            comment: None,
+            duplicates: DuplicatePolicy::default(),
+            keep_history: None,
+            refresh: None,
+            priority: 0,
+            allow_shared_songs: false,
+            min_days_since_last_generated: None,
+            recently_generated_penalty: None,
+            rotation: None,
+            selection: None,
+            optimize: None,
         }
     }
 }
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// How a playlist's songs changed between two generations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PlaylistDiff {
+    /// Songs in both generations, in their new order
+    pub kept: Vec<String>,
+    /// Songs only in the new generation
+    pub added: Vec<String>,
+    /// Songs only in the previous generation
+    pub removed: Vec<String>,
+    /// Kept songs whose position among the kept songs changed
+    pub moved: Vec<MovedSong>,
+}
+
+/// A kept song that changed position. Positions are 0-based and count only kept
+/// songs, so additions and removals around it do not make it look moved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MovedSong {
+    pub id: String,
+    pub from: usize,
+    pub to: usize,
+}
+
+impl PlaylistDiff {
+    /// Compare the previous song IDs of a playlist with the new ones
+    pub fn between(previous: &[String], new: &[String]) -> Self {
+        let previous_set: HashSet<&String> = previous.iter().collect();
+        let new_set: HashSet<&String> = new.iter().collect();
+
+        let kept: Vec<String> = new
+            .iter()
+            .filter(|id| previous_set.contains(id))
+            .cloned()
+            .collect();
+        let added = new
+            .iter()
+            .filter(|id| !previous_set.contains(id))
+            .cloned()
+            .collect();
+        let removed = previous
+            .iter()
+            .filter(|id| !new_set.contains(id))
+            .cloned()
+            .collect();
+
+        let previous_positions: HashMap<&String, usize> = previous
+            .iter()
+            .filter(|id| new_set.contains(id))
+            .enumerate()
+            .map(|(position, id)| (id, position))
+            .collect();
+        let moved = kept
+            .iter()
+            .enumerate()
+            .filter_map(|(to, id)| {
+                let from = previous_positions[id];
+                (from != to).then(|| MovedSong {
+                    id: id.clone(),
+                    from,
+                    to,
+                })
+            })
+            .collect();
+
+        Self {
+            kept,
+            added,
+            removed,
+            moved,
+        }
+    }
+
+    /// Share of the new playlist made up of songs that were not in the previous one
+    pub fn turnover(&self) -> f32 {
+        let total = self.kept.len() + self.added.len();
+        if total == 0 {
+            0.0
+        } else {
+            self.added.len() as f32 / total as f32
+        }
+    }
+
+    /// Whether the new generation is identical to the previous one
+    pub fn is_unchanged(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+impl fmt::Display for PlaylistDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} kept ({} moved), {} added, {} removed ({:.0}% new)",
+            self.kept.len(),
+            self.moved.len(),
+            self.added.len(),
+            self.removed.len(),
+            self.turnover() * 100.0
+        )
+    }
+}
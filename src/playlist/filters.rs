@@ -164,8 +164,8 @@ impl SongFilters {
             return true;
         };
 
-        let min_ok = range.min.map_or(true, |m| year >= m);
-        let max_ok = range.max.map_or(true, |m| year <= m);
+        let min_ok = range.min.is_none_or(|m| year >= m);
+        let max_ok = range.max.is_none_or(|m| year <= m);
         min_ok && max_ok
     }
 
@@ -176,7 +176,7 @@ impl SongFilters {
             return true;
         };
 
-        let result = match play_count_filter {
+        match play_count_filter {
             PlayCountFilter::None => true,
             
             PlayCountFilter::Exact { count } => {
@@ -188,8 +188,8 @@ impl SongFilters {
             
             PlayCountFilter::Range { min, max } => {
                 let song_play_count = song.play_count.unwrap_or(0);
-                let min_ok = min.map_or(true, |m| song_play_count >= m);
-                let max_ok = max.map_or(true, |m| song_play_count <= m);
+                let min_ok = min.is_none_or(|m| song_play_count >= m);
+                let max_ok = max.is_none_or(|m| song_play_count <= m);
                 min_ok && max_ok
             },
             
@@ -230,8 +230,7 @@ impl SongFilters {
                     _ => true, // Invalid direction, default to accepting
                 }
             }
-        };
-        result
+        }
     }
 
     /// Apply all filters to determine if a song should be included
@@ -1,18 +1,76 @@
 use super::filters::SongFilters;
+use super::incremental::{IncrementalQuality, SongFeatures};
+use super::optimizer::PlaylistOptimizer;
 use super::scoring::PlaylistScoring;
+use super::selection::CandidateSelection;
 use super::transitions::PlaylistTransitions;
-use super::{Playlist, PlaylistConfig, PlaylistSong};
+use super::{OptimizationSettings, Playlist, PlaylistConfig, PlaylistSong};
 use crate::models::Song;
-use crate::playlist::utils::PlaylistNaming;
+use crate::playlist::utils::{PlaylistComment, PlaylistNaming};
+use chrono::{DateTime, Utc};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 
 /// Main playlist generator
 pub struct PlaylistGenerator {
     config: PlaylistConfig,
+    /// Days since each song was last published in this config's playlist
+    days_since_generated: HashMap<String, f32>,
+    /// Days since each song was last published in any generated playlist
+    days_since_surfaced: HashMap<String, f32>,
+    /// Source of all randomness, so a seeded generator reproduces its playlists
+    rng: RefCell<StdRng>,
+    /// Reference time for play recency, the playlist name and the comment's date
+    now: DateTime<Utc>,
+}
+
+/// Which hard constraints a pass of `extend_playlist_iteratively` enforces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Constraints {
+    /// Choosing songs: the ordering rules plus `min_days_since_last_play`
+    Selection,
+    /// Ordering songs that were already chosen: only the artist, album and BPM-jump rules
+    Ordering,
 }
 
 impl PlaylistGenerator {
     pub fn new(config: PlaylistConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            days_since_generated: HashMap::new(),
+            days_since_surfaced: HashMap::new(),
+            rng: RefCell::new(StdRng::from_entropy()),
+            now: Utc::now(),
+        }
+    }
+
+    /// Use a seeded random number generator for preference jitter, stochastic
+    /// selection and naming
+    pub fn with_rng(mut self, rng: StdRng) -> Self {
+        self.rng = RefCell::new(rng);
+        self
+    }
+
+    /// Generate as of `now` instead of the current time, so a rerun with the same seed
+    /// on a later day reproduces the playlists
+    pub fn with_reference_time(mut self, now: DateTime<Utc>) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Provide when songs were last published in this config's playlist, for the
+    /// `min_days_since_last_generated` rule
+    pub fn with_days_since_generated(mut self, days_since_generated: HashMap<String, f32>) -> Self {
+        self.days_since_generated = days_since_generated;
+        self
+    }
+
+    /// Provide when songs were last published in any generated playlist, for `rotation`
+    pub fn with_days_since_surfaced(mut self, days_since_surfaced: HashMap<String, f32>) -> Self {
+        self.days_since_surfaced = days_since_surfaced;
+        self
     }
 
     /// Generate a playlist from a collection of songs
@@ -23,7 +81,251 @@ impl PlaylistGenerator {
         target_length: Option<usize>,
     ) -> Playlist {
         let target_length = target_length.unwrap_or(20);
+        let filtered_songs = self.candidate_songs(songs);
+
+        // Experimental: Use iterative quality-aware generation instead of simple ordering
+        let ordered_songs = if self.config.optimize.is_some() {
+            let ordered_songs =
+                self.generate_playlist_iteratively(filtered_songs.clone(), target_length);
+            self.optimize(ordered_songs, &filtered_songs)
+        } else {
+            self.generate_playlist_iteratively(filtered_songs, target_length)
+        };
+
+        self.finish_playlist(ordered_songs, playlist_name)
+    }
+
+    /// Evolve an existing playlist instead of starting over: keep its best songs by
+    /// preference score, replace the rest (`refresh.replace_fraction` of the target
+    /// length, plus any that no longer match the filters) with new picks, then reorder
+    /// everything with the normal transition rules.
+    pub fn refresh_playlist(
+        &self,
+        songs: Vec<Song>,
+        previous: &[Song],
+        playlist_name: Option<String>,
+        target_length: Option<usize>,
+    ) -> Playlist {
+        let target_length = target_length.unwrap_or(20);
+        let replace_fraction = self
+            .config
+            .refresh
+            .as_ref()
+            .map_or(1.0, |refresh| refresh.replace_fraction.clamp(0.0, 1.0));
+        let keep_count = ((1.0 - replace_fraction) * target_length as f32).round() as usize;
+
+        // Keep the best-scoring songs that still match the config, in their current order
+        let mut still_matching: Vec<(usize, f32, &Song)> = previous
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| SongFilters::should_include_song(song, &self.config))
+            .map(|(position, song)| (position, self.preference_score(song), song))
+            .collect();
+        still_matching.sort_by(|(_, score_a, _), (_, score_b, _)| {
+            score_b
+                .partial_cmp(score_a)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        still_matching.truncate(keep_count);
+        still_matching.sort_by_key(|(position, _, _)| *position);
+        let kept: Vec<PlaylistSong> = still_matching
+            .into_iter()
+            .map(|(_, _, song)| PlaylistSong::new(song.clone()))
+            .collect();
+        let kept_ids: HashSet<String> = kept.iter().map(|ps| ps.song.id.clone()).collect();
+
+        // Replacements never bring back a song that was just dropped
+        let previous_ids: HashSet<&str> = previous.iter().map(|s| s.id.as_str()).collect();
+        let candidates: Vec<Song> = self
+            .candidate_songs(songs)
+            .into_iter()
+            .filter(|song| !previous_ids.contains(song.id.as_str()))
+            .collect();
+        let selected = self.extend_playlist_iteratively(
+            kept,
+            candidates.clone(),
+            target_length,
+            Constraints::Selection,
+        );
+
+        // Reorder the combined set from scratch with the ordering rules only: kept songs
+        // were usually played recently, which must not push them out of the playlist
+        let mut pool: Vec<Song> = selected.into_iter().map(|ps| ps.song).collect();
+        self.sort_by_preference(&mut pool);
+        let pool_size = pool.len();
+        let ordered_songs = self.extend_playlist_iteratively(
+            Vec::new(),
+            pool.clone(),
+            pool_size,
+            Constraints::Ordering,
+        );
+        let ordered_songs =
+            self.place_remaining(ordered_songs, pool, candidates, &kept_ids, target_length);
+        // The kept songs must stay, so the optimizer may only reorder
+        let ordered_songs = self.optimize(ordered_songs, &[]);
+        let kept_count = ordered_songs
+            .iter()
+            .filter(|ps| kept_ids.contains(&ps.song.id))
+            .count();
+
+        println!(
+            "Refreshed '{}': kept {} of {} songs, added {} (target: {})",
+            self.config.name,
+            kept_count,
+            previous.len(),
+            ordered_songs.len() - kept_count,
+            target_length
+        );
+
+        self.finish_playlist(ordered_songs, playlist_name)
+    }
+
+    /// Fit in the songs of `pool` the ordering pass could not place. The optimizer first
+    /// tries to reorder the whole list without violations; if that fails, a search places
+    /// every kept song and fills the other slots from the newly added songs and unused
+    /// `candidates`, dropping the least preferred kept song while no order is found.
+    fn place_remaining(
+        &self,
+        ordered_songs: Vec<PlaylistSong>,
+        pool: Vec<Song>,
+        candidates: Vec<Song>,
+        kept_ids: &HashSet<String>,
+        target_length: usize,
+    ) -> Vec<PlaylistSong> {
+        let placed: HashSet<&str> = ordered_songs.iter().map(|ps| ps.song.id.as_str()).collect();
+        if pool.iter().all(|song| placed.contains(song.id.as_str())) {
+            return ordered_songs;
+        }
 
+        let settings = self.config.optimize.clone().unwrap_or_default();
+        let reorder_only = OptimizationSettings {
+            replacement_candidates: 0,
+            ..settings.clone()
+        };
+        let optimizer = PlaylistOptimizer::new(&self.config, &reorder_only, self.now);
+        let songs = optimizer.optimize(pool.clone(), &[]).songs;
+        let songs = if optimizer.violations(&songs) == 0 {
+            songs
+        } else {
+            // The pool is sorted by preference, so kept songs are too
+            let (mut kept, mut fillers): (Vec<Song>, Vec<Song>) = pool
+                .into_iter()
+                .partition(|song| kept_ids.contains(&song.id));
+            let length = kept.len() + fillers.len();
+            let in_pool: HashSet<String> =
+                kept.iter().chain(&fillers).map(|s| s.id.clone()).collect();
+            fillers.extend(
+                candidates
+                    .iter()
+                    .filter(|song| !in_pool.contains(&song.id))
+                    .filter(|song| !self.would_violate_min_days_since_last_play(song))
+                    .take(settings.replacement_candidates)
+                    .cloned(),
+            );
+            loop {
+                if let Some(songs) = self.search_order(&kept, &fillers, length) {
+                    break songs;
+                }
+                if kept.pop().is_none() {
+                    break Vec::new();
+                }
+            }
+        };
+
+        let in_playlist: HashSet<String> = songs.iter().map(|song| song.id.clone()).collect();
+        let unused: Vec<Song> = candidates
+            .into_iter()
+            .filter(|song| !in_playlist.contains(&song.id))
+            .collect();
+        self.extend_playlist_iteratively(
+            songs.into_iter().map(PlaylistSong::new).collect(),
+            unused,
+            target_length,
+            Constraints::Selection,
+        )
+    }
+
+    /// Depth-first search for an order of all of `kept` plus `fillers`, up to `length`
+    /// songs, that breaks no ordering rule. Kept songs are tried first at every position
+    /// and both lists in their given order.
+    fn search_order(&self, kept: &[Song], fillers: &[Song], length: usize) -> Option<Vec<Song>> {
+        let length = length.min(kept.len() + fillers.len());
+        if length < kept.len() {
+            return None;
+        }
+        let songs: Vec<&Song> = kept.iter().chain(fillers).collect();
+        let mut used = vec![false; songs.len()];
+        let mut order = Vec::with_capacity(length);
+        let mut steps = 0;
+        let found = self.extend_order(
+            &songs,
+            kept.len(),
+            &mut used,
+            &mut order,
+            length,
+            &mut steps,
+        );
+        found.then_some(order)
+    }
+
+    /// One level of `search_order`: the first `kept_count` of `songs` must all be placed.
+    /// Gives up after a fixed number of steps over the whole search.
+    fn extend_order(
+        &self,
+        songs: &[&Song],
+        kept_count: usize,
+        used: &mut [bool],
+        order: &mut Vec<Song>,
+        length: usize,
+        steps: &mut usize,
+    ) -> bool {
+        const MAX_STEPS: usize = 50_000;
+
+        let kept_left = used[..kept_count].iter().filter(|used| !**used).count();
+        if order.len() == length {
+            return kept_left == 0;
+        }
+        let fillers_allowed = length - order.len() > kept_left;
+
+        for (i, song) in songs.iter().enumerate() {
+            if used[i]
+                || (i >= kept_count && !fillers_allowed)
+                || self.breaks_ordering_rules(order, song)
+            {
+                continue;
+            }
+            *steps += 1;
+            if *steps > MAX_STEPS {
+                return false;
+            }
+
+            used[i] = true;
+            order.push((*song).clone());
+            if self.extend_order(songs, kept_count, used, order, length, steps) {
+                return true;
+            }
+            order.pop();
+            used[i] = false;
+        }
+        false
+    }
+
+    /// Whether appending `candidate` to `playlist` breaks the artist, album or BPM jump rules
+    fn breaks_ordering_rules(&self, playlist: &[Song], candidate: &Song) -> bool {
+        let bpm_jump = match (playlist.last().and_then(|song| song.bpm), candidate.bpm) {
+            (Some(bpm_a), Some(bpm_b)) => {
+                (bpm_a as i32 - bpm_b as i32).unsigned_abs()
+                    > self.config.transition_rules.max_bpm_jump
+            }
+            _ => false,
+        };
+        bpm_jump
+            || self.would_violate_artist_repetition(playlist, candidate)
+            || self.would_violate_album_repetition(playlist, candidate)
+    }
+
+    /// Songs that pass the config's filters, best preference score first
+    fn candidate_songs(&self, songs: Vec<Song>) -> Vec<Song> {
         // Filter songs and remove non-songs
         // First pass: basic filters (genre, BPM, etc.)
         let basic_filtered_songs: Vec<Song> = songs
@@ -35,21 +337,105 @@ impl PlaylistGenerator {
         let mut filtered_songs: Vec<Song> = basic_filtered_songs
             .iter()
             .filter(|song| SongFilters::should_include_song_with_play_count_filter(song, &self.config, &basic_filtered_songs))
+            .filter(|song| !self.would_violate_min_days_since_last_generated(song))
             .cloned()
             .collect();
 
         // Sort songs by preference score using configurable weights
-        filtered_songs.sort_by(|a, b| {
-            let score_a = PlaylistScoring::calculate_preference_score(a, &self.config);
-            let score_b = PlaylistScoring::calculate_preference_score(b, &self.config);
+        self.sort_by_preference(&mut filtered_songs);
+        filtered_songs
+    }
+
+    /// Sort songs best preference score first. Each song is scored once, so its
+    /// random jitter is the same for every comparison.
+    fn sort_by_preference(&self, songs: &mut Vec<Song>) {
+        let mut scored: Vec<(f32, Song)> = songs
+            .drain(..)
+            .map(|song| (self.preference_score(&song), song))
+            .collect();
+        scored.sort_by(|(score_a, _), (score_b, _)| {
             score_b
-                .partial_cmp(&score_a)
+                .partial_cmp(score_a)
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
+        songs.extend(scored.into_iter().map(|(_, song)| song));
+    }
 
-        // Experimental: Use iterative quality-aware generation instead of simple ordering
-        let ordered_songs = self.generate_playlist_iteratively(filtered_songs, target_length);
+    /// The configured preference score with its random jitter, adjusted by when the
+    /// song was last generated
+    fn preference_score(&self, song: &Song) -> f32 {
+        let jitter = PlaylistScoring::preference_jitter(&self.config, &mut *self.rng.borrow_mut());
+        PlaylistScoring::calculate_preference_score(song, &self.config, self.now)
+            + jitter
+            + self.rotation_bonus(song)
+            - self.recently_generated_penalty(song)
+    }
+
+    /// Fairness bonus for songs no generated playlist surfaced lately: the full `weight`
+    /// for songs not surfaced within the rotation period, less for more recent ones
+    fn rotation_bonus(&self, song: &Song) -> f32 {
+        let Some(rotation) = &self.config.rotation else {
+            return 0.0;
+        };
+        let staleness = match self.days_since_surfaced.get(&song.id) {
+            Some(&days) if rotation.period_days > 0 => {
+                (days / rotation.period_days as f32).min(1.0)
+            }
+            _ => 1.0,
+        };
+        rotation.weight * staleness
+    }
 
+    /// Run the optimizer's second pass if the config enables it, rescoring the songs'
+    /// transitions and quality contributions for their new positions
+    fn optimize(&self, ordered_songs: Vec<PlaylistSong>, candidates: &[Song]) -> Vec<PlaylistSong> {
+        let Some(settings) = &self.config.optimize else {
+            return ordered_songs;
+        };
+        if ordered_songs.len() < 2 {
+            return ordered_songs;
+        }
+
+        let songs: Vec<Song> = ordered_songs.into_iter().map(|ps| ps.song).collect();
+        let optimized =
+            PlaylistOptimizer::new(&self.config, settings, self.now).optimize(songs, candidates);
+        println!(
+            "Optimized '{}': score {:.3} -> {:.3} ({} changes{})",
+            self.config.name,
+            optimized.score_before,
+            optimized.score_after,
+            optimized.moves,
+            if optimized.timed_out {
+                ", stopped at the time budget"
+            } else {
+                ""
+            }
+        );
+
+        let songs = optimized.songs;
+        let mut quality = IncrementalQuality::new(&self.config);
+        (0..songs.len())
+            .map(|i| {
+                let features = quality.features(&songs[i]);
+                let transition_score = quality.transition_score(
+                    i.checked_sub(1).map(|j| &songs[j]),
+                    &songs[i],
+                    &features,
+                );
+                let previous_quality = quality.score();
+                quality.push(&features);
+                let contribution = quality.score() - previous_quality;
+                PlaylistSong::with_metadata(songs[i].clone(), transition_score, contribution)
+            })
+            .collect()
+    }
+
+    /// Score the ordered songs and wrap them with name, metadata and comment
+    fn finish_playlist(
+        &self,
+        ordered_songs: Vec<PlaylistSong>,
+        playlist_name: Option<String>,
+    ) -> Playlist {
         // Extract songs for metadata calculation
         let songs_for_metadata: Vec<Song> =
             ordered_songs.iter().map(|ps| ps.song.clone()).collect();
@@ -57,15 +443,32 @@ impl PlaylistGenerator {
         let quality_score =
             PlaylistScoring::calculate_quality_score(&songs_for_metadata, &metadata, &self.config);
 
+        let comment = PlaylistComment::render(
+            self.config
+                .comment
+                .as_deref()
+                .unwrap_or(PlaylistComment::DEFAULT_TEMPLATE),
+            &self.config.name,
+            &metadata,
+            quality_score,
+            self.now,
+        );
+
         Playlist {
             songs: ordered_songs,
             name: PlaylistNaming::generate_playlist_name(
                 playlist_name.unwrap_or("Daylist".to_string()),
                 &metadata,
+                self.now,
+                &mut *self.rng.borrow_mut(),
             ),
             base_name_pattern: self.config.name.clone(),
             metadata,
             quality_score,
+            comment,
+            public: self.config.public,
+            duplicates: self.config.duplicates,
+            keep_history: self.config.keep_history,
         }
     }
 
@@ -75,27 +478,58 @@ impl PlaylistGenerator {
         candidate_songs: Vec<Song>,
         target_length: usize,
     ) -> Vec<PlaylistSong> {
-        let mut playlist: Vec<PlaylistSong> = Vec::new();
+        let playlist = self.extend_playlist_iteratively(
+            Vec::new(),
+            candidate_songs,
+            target_length,
+            Constraints::Selection,
+        );
+
+        // Log summary of playlist generation
+        if !playlist.is_empty() {
+            println!(
+                "Generated {} songs for '{}' (target: {})",
+                playlist.len(),
+                self.config.name,
+                target_length
+            );
+        }
+
+        playlist
+    }
+
+    /// Append the best candidates to `playlist` one at a time until it reaches the
+    /// target length or no candidate satisfies the hard constraints
+    fn extend_playlist_iteratively(
+        &self,
+        mut playlist: Vec<PlaylistSong>,
+        candidate_songs: Vec<Song>,
+        target_length: usize,
+        constraints: Constraints,
+    ) -> Vec<PlaylistSong> {
         let mut remaining_songs = candidate_songs; // Keep persistent list of remaining songs
-        
+
+        // Running aggregates of the working playlist, so scoring a candidate doesn't
+        // rebuild the playlist's metadata
+        let mut quality = IncrementalQuality::new(&self.config);
+        let mut current_playlist_songs: Vec<Song> = Vec::with_capacity(target_length);
+        for playlist_song in &playlist {
+            let features = quality.features(&playlist_song.song);
+            quality.push(&features);
+            current_playlist_songs.push(playlist_song.song.clone());
+        }
+        let mut remaining_features: Vec<SongFeatures> = remaining_songs
+            .iter()
+            .map(|song| quality.features(song))
+            .collect();
+
         while playlist.len() < target_length && !remaining_songs.is_empty() {
-            let mut best_candidate_index: Option<usize> = None;
-            let mut best_quality_score = 0.0;
-            let mut best_transition_score = 0.0;
-
-            // Calculate current playlist quality for comparison
-            let current_playlist_songs: Vec<Song> =
-                playlist.iter().map(|ps| ps.song.clone()).collect();
-            let current_quality = if current_playlist_songs.is_empty() {
-                0.0
-            } else {
-                let current_metadata = PlaylistScoring::calculate_metadata(&current_playlist_songs);
-                PlaylistScoring::calculate_quality_score(
-                    &current_playlist_songs,
-                    &current_metadata,
-                    &self.config,
-                )
-            };
+            // (index in remaining_songs, combined score, transition score) of every
+            // candidate that passes the hard constraints
+            let mut scored_candidates: Vec<(usize, f32, f32)> = Vec::new();
+
+            // Current playlist quality for comparison
+            let current_quality = quality.score();
 
             // Try candidates in order of preference score (already sorted)
             for (i, candidate) in remaining_songs.iter().enumerate() {
@@ -118,58 +552,57 @@ impl PlaylistGenerator {
                 }
 
                 // BPM transition constraint: Use configured max_bpm_jump as hard constraint
-                if let Some(last_song) = current_playlist_songs.last() {
-                    if let (Some(bpm_a), Some(bpm_b)) = (last_song.bpm, candidate.bpm) {
-                        let bpm_diff = (bpm_a as i32 - bpm_b as i32).abs() as u32;
-                        if bpm_diff > self.config.transition_rules.max_bpm_jump {
-                            continue;
-                        }
+                if let Some(last_song) = current_playlist_songs.last()
+                    && let (Some(bpm_a), Some(bpm_b)) = (last_song.bpm, candidate.bpm)
+                {
+                    let bpm_diff = (bpm_a as i32 - bpm_b as i32).unsigned_abs();
+                    if bpm_diff > self.config.transition_rules.max_bpm_jump {
+                        continue;
                     }
                 }
 
                 // Hard constraint: Skip candidates that would violate minimum days since last play
-                if self.would_violate_min_days_since_last_play(candidate) {
+                if constraints == Constraints::Selection
+                    && self.would_violate_min_days_since_last_play(candidate)
+                {
                     continue;
                 }
 
-                // Calculate transition score for this candidate against the working playlist
+                // Transition score against the working playlist, and the quality the
+                // playlist would have with this candidate added at the end
+                let features = &remaining_features[i];
                 let transition_score =
-                    self.calculate_playlist_transition_score(&current_playlist_songs, candidate);
-
-                // Create a test playlist with this candidate added at the end (simpler approach)
-                let mut test_playlist = current_playlist_songs.clone();
-                test_playlist.push(candidate.clone());
-
-                // Calculate quality of the test playlist
-                let test_metadata = PlaylistScoring::calculate_metadata(&test_playlist);
-                let test_quality = PlaylistScoring::calculate_quality_score(
-                    &test_playlist,
-                    &test_metadata,
-                    &self.config,
-                );
+                    quality.transition_score(current_playlist_songs.last(), candidate, features);
+                let test_quality = quality.score_with(features);
 
                 // Combine quality score with transition score
                 // Use configurable quality vs transition weighting (70/30 split for now)
                 let combined_score = test_quality * 0.7 + transition_score * 0.3;
 
-                // Always consider the candidate - just pick the best one available
-                let should_accept = best_candidate_index.is_none() || combined_score > best_quality_score;
-
-                if should_accept {
-                    best_candidate_index = Some(i);
-                    best_quality_score = combined_score;
-                    best_transition_score = transition_score;
-                }
+                scored_candidates.push((i, combined_score, transition_score));
             }
 
-            // Add the best candidate we found
-            if let Some(index) = best_candidate_index {
+            // Take the best candidate, or draw one if the config sets a temperature
+            let combined_scores: Vec<f32> = scored_candidates
+                .iter()
+                .map(|&(_, combined_score, _)| combined_score)
+                .collect();
+            let chosen = CandidateSelection::choose(
+                &combined_scores,
+                self.config.selection.as_ref(),
+                &mut *self.rng.borrow_mut(),
+            );
+
+            if let Some(chosen) = chosen {
+                let (index, combined_score, transition_score) = scored_candidates[chosen];
                 let chosen_song = remaining_songs.remove(index);
-                let quality_contribution = best_quality_score - current_quality;
+                quality.push(&remaining_features.remove(index));
+                current_playlist_songs.push(chosen_song.clone());
+                let quality_contribution = combined_score - current_quality;
                 // Since we're building iteratively, just append to the end
                 playlist.push(PlaylistSong::with_metadata(
                     chosen_song,
-                    best_transition_score,
+                    transition_score,
                     quality_contribution,
                 ));
             } else {
@@ -178,16 +611,6 @@ impl PlaylistGenerator {
             }
         }
 
-        // Log summary of playlist generation
-        if !playlist.is_empty() {
-            println!(
-                "Generated {} songs for '{}' (target: {})",
-                playlist.len(),
-                self.config.name,
-                target_length
-            );
-        }
-
         playlist
     }
 
@@ -212,18 +635,42 @@ impl PlaylistGenerator {
     /// Check if a candidate would violate the minimum days since last play rule
     fn would_violate_min_days_since_last_play(&self, candidate: &Song) -> bool {
         if let Some(min_days) = self.config.min_days_since_last_play {
-            PlaylistTransitions::would_violate_min_days_since_last_play(min_days, candidate)
+            PlaylistTransitions::would_violate_min_days_since_last_play(
+                min_days, candidate, self.now,
+            )
         } else {
             false // No rule to enforce if min_days_since_last_play is not set
         }
     }
 
-    /// Calculate how well a candidate song would fit with the current working playlist
-    fn calculate_playlist_transition_score(
-        &self,
-        current_playlist: &[Song],
-        candidate: &Song,
-    ) -> f32 {
-        PlaylistTransitions::calculate_transition_score(&self.config, current_playlist, candidate)
+    /// Check if a candidate was published in this playlist too recently. Only a hard
+    /// constraint when no `recently_generated_penalty` is configured.
+    fn would_violate_min_days_since_last_generated(&self, candidate: &Song) -> bool {
+        let (Some(min_days), None) = (
+            self.config.min_days_since_last_generated,
+            self.config.recently_generated_penalty,
+        ) else {
+            return false;
+        };
+        self.days_since_generated
+            .get(&candidate.id)
+            .is_some_and(|&days| days < min_days as f32)
+    }
+
+    /// Preference penalty for a song published recently in this playlist, shrinking
+    /// linearly to zero at `min_days_since_last_generated`
+    fn recently_generated_penalty(&self, song: &Song) -> f32 {
+        let (Some(min_days), Some(penalty)) = (
+            self.config.min_days_since_last_generated,
+            self.config.recently_generated_penalty,
+        ) else {
+            return 0.0;
+        };
+        match self.days_since_generated.get(&song.id) {
+            Some(&days) if min_days > 0 && days < min_days as f32 => {
+                penalty * (1.0 - days / min_days as f32)
+            }
+            _ => 0.0,
+        }
     }
 }
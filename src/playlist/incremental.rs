@@ -0,0 +1,318 @@
+use super::config::PlaylistConfig;
+use super::scoring::PlaylistScoring;
+use super::transitions::PlaylistTransitions;
+use crate::models::Song;
+use std::collections::HashMap;
+
+/// What the incremental scorer needs to know about a song, with genres and artist
+/// interned as indices. Computed once per candidate so scoring it never allocates.
+#[derive(Debug, Clone)]
+pub struct SongFeatures {
+    genres: Vec<usize>,
+    artist: usize,
+    bpm: Option<u32>,
+    year: Option<u32>,
+    play_count: Option<u32>,
+}
+
+/// Running aggregates of a playlist being built, to score appending a candidate
+/// without rebuilding `PlaylistMetadata`. Gives the same results as
+/// `PlaylistScoring::calculate_quality_score` and
+/// `PlaylistTransitions::calculate_genre_compatibility_score`, up to float rounding.
+pub struct IncrementalQuality<'a> {
+    config: &'a PlaylistConfig,
+    genre_ids: HashMap<String, usize>,
+    artist_ids: HashMap<String, usize>,
+
+    len: usize,
+    /// Songs per genre, indexed by genre ID
+    genre_counts: Vec<usize>,
+    distinct_genres: usize,
+    /// Sum of the genre counts and of `count * log2(count)`, for the entropy
+    genre_total: usize,
+    genre_count_log_sum: f64,
+    /// Songs per artist, indexed by artist ID
+    artist_counts: Vec<usize>,
+    distinct_artists: usize,
+    last_bpm: Option<u32>,
+    bpm_jump_sum: u64,
+    bpm_jump_count: usize,
+    play_count_n: usize,
+    play_count_sum: f64,
+    play_count_square_sum: f64,
+    year_range: Option<(u32, u32)>,
+}
+
+fn count_log(count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        count as f64 * (count as f64).log2()
+    }
+}
+
+impl<'a> IncrementalQuality<'a> {
+    pub fn new(config: &'a PlaylistConfig) -> Self {
+        Self {
+            config,
+            genre_ids: HashMap::new(),
+            artist_ids: HashMap::new(),
+            len: 0,
+            genre_counts: Vec::new(),
+            distinct_genres: 0,
+            genre_total: 0,
+            genre_count_log_sum: 0.0,
+            artist_counts: Vec::new(),
+            distinct_artists: 0,
+            last_bpm: None,
+            bpm_jump_sum: 0,
+            bpm_jump_count: 0,
+            play_count_n: 0,
+            play_count_sum: 0.0,
+            play_count_square_sum: 0.0,
+            year_range: None,
+        }
+    }
+
+    /// Number of songs added so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Extract a song's features, registering its genres and artist
+    pub fn features(&mut self, song: &Song) -> SongFeatures {
+        let genres = song
+            .get_all_genres()
+            .into_iter()
+            .map(|genre| {
+                let next_id = self.genre_ids.len();
+                *self.genre_ids.entry(genre).or_insert(next_id)
+            })
+            .collect();
+        let next_id = self.artist_ids.len();
+        let artist = *self
+            .artist_ids
+            .entry(song.artist.clone())
+            .or_insert(next_id);
+
+        SongFeatures {
+            genres,
+            artist,
+            bpm: song.bpm,
+            year: song.year,
+            play_count: song.play_count,
+        }
+    }
+
+    /// Append a song to the playlist
+    pub fn push(&mut self, song: &SongFeatures) {
+        if self.genre_counts.len() < self.genre_ids.len() {
+            self.genre_counts.resize(self.genre_ids.len(), 0);
+        }
+        if self.artist_counts.len() < self.artist_ids.len() {
+            self.artist_counts.resize(self.artist_ids.len(), 0);
+        }
+
+        for &genre in &song.genres {
+            let count = self.genre_counts[genre];
+            self.genre_count_log_sum += count_log(count + 1) - count_log(count);
+            if count == 0 {
+                self.distinct_genres += 1;
+            }
+            self.genre_counts[genre] += 1;
+        }
+        self.genre_total += song.genres.len();
+
+        if self.artist_counts[song.artist] == 0 {
+            self.distinct_artists += 1;
+        }
+        self.artist_counts[song.artist] += 1;
+
+        if self.len > 0
+            && let Some(jump) = Self::bpm_jump(self.last_bpm, song.bpm)
+        {
+            self.bpm_jump_sum += jump as u64;
+            self.bpm_jump_count += 1;
+        }
+        self.last_bpm = song.bpm;
+
+        if let Some(play_count) = song.play_count {
+            self.play_count_n += 1;
+            self.play_count_sum += play_count as f64;
+            self.play_count_square_sum += (play_count as f64).powi(2);
+        }
+
+        self.year_range = Self::extend_year_range(self.year_range, song.year);
+        self.len += 1;
+    }
+
+    /// Quality score of the playlist so far
+    pub fn score(&self) -> f32 {
+        self.quality(None)
+    }
+
+    /// Quality score the playlist would have with `candidate` appended
+    pub fn score_with(&self, candidate: &SongFeatures) -> f32 {
+        self.quality(Some(candidate))
+    }
+
+    /// How well the candidate's genres fit the playlist so far, as in
+    /// `PlaylistTransitions::calculate_genre_compatibility_score`
+    pub fn genre_compatibility(&self, candidate: &SongFeatures) -> f32 {
+        if self.len == 0 || candidate.genres.is_empty() || self.distinct_genres == 0 {
+            return 0.5;
+        }
+        let best_count = candidate
+            .genres
+            .iter()
+            .map(|&genre| self.genre_count(genre))
+            .max()
+            .unwrap_or(0);
+        let best_frequency =
+            (best_count > 0).then(|| (best_count as f32 / self.len as f32).min(1.0));
+        PlaylistTransitions::genre_compatibility_for_frequency(self.config, best_frequency)
+    }
+
+    /// Transition score of appending `candidate` after `last_song`, as in
+    /// `PlaylistTransitions::calculate_transition_score`
+    pub fn transition_score(
+        &self,
+        last_song: Option<&Song>,
+        candidate: &Song,
+        features: &SongFeatures,
+    ) -> f32 {
+        match last_song {
+            Some(last_song) if self.len > 0 => {
+                let bpm_score = PlaylistTransitions::calculate_bpm_transition_score(
+                    self.config,
+                    last_song,
+                    candidate,
+                );
+                (bpm_score + self.genre_compatibility(features)) / 2.0
+            }
+            _ => 0.5, // Neutral score for first song
+        }
+    }
+
+    fn quality(&self, added: Option<&SongFeatures>) -> f32 {
+        let len = self.len + usize::from(added.is_some());
+        if len == 0 {
+            return 0.0;
+        }
+        let weights = &self.config.quality_weights;
+
+        let genre_coherence = self.genre_coherence(added, len) * weights.genre_coherence;
+        let era_span = match Self::extend_year_range(self.year_range, added.and_then(|s| s.year)) {
+            Some((min_year, max_year)) => (Some(min_year), Some(max_year)),
+            None => (None, None),
+        };
+        let era_cohesion =
+            PlaylistScoring::calculate_era_cohesion_score(&era_span) * weights.era_cohesion;
+        let popularity_balance = self.popularity_balance(added) * weights.popularity_balance;
+        let artist_diversity = self.artist_diversity(added, len) * weights.artist_diversity;
+        let bpm_smoothness = self.bpm_smoothness(added, len) * weights.bpm_transition_smoothness;
+
+        let total_score =
+            genre_coherence + popularity_balance + era_cohesion + artist_diversity + bpm_smoothness;
+        PlaylistScoring::normalize_quality_score(total_score, self.config)
+    }
+
+    fn genre_coherence(&self, added: Option<&SongFeatures>, len: usize) -> f32 {
+        let mut distinct_genres = self.distinct_genres;
+        let mut genre_total = self.genre_total;
+        let mut count_log_sum = self.genre_count_log_sum;
+        if let Some(added) = added {
+            for &genre in &added.genres {
+                let count = self.genre_count(genre);
+                count_log_sum += count_log(count + 1) - count_log(count);
+                if count == 0 {
+                    distinct_genres += 1;
+                }
+            }
+            genre_total += added.genres.len();
+        }
+
+        match distinct_genres {
+            0 => 0.5, // Neutral when no data
+            1 => 1.0, // Maximum coherence - single genre
+            _ => {
+                // -sum(p * log2(p)) with p = count / len
+                let len = len as f64;
+                let entropy = (genre_total as f64 / len) * len.log2() - count_log_sum / len;
+                PlaylistScoring::genre_coherence_for_entropy(entropy as f32, distinct_genres)
+            }
+        }
+    }
+
+    fn popularity_balance(&self, added: Option<&SongFeatures>) -> f32 {
+        let mut n = self.play_count_n;
+        let mut sum = self.play_count_sum;
+        let mut square_sum = self.play_count_square_sum;
+        if let Some(play_count) = added.and_then(|s| s.play_count) {
+            n += 1;
+            sum += play_count as f64;
+            square_sum += (play_count as f64).powi(2);
+        }
+
+        if n <= 1 {
+            return 0.5; // Neutral without enough play count data
+        }
+        let mean = sum / n as f64;
+        let variance = (square_sum / n as f64 - mean * mean).max(0.0);
+        PlaylistScoring::popularity_balance_for_moments(mean as f32, variance as f32)
+    }
+
+    fn artist_diversity(&self, added: Option<&SongFeatures>, len: usize) -> f32 {
+        if len <= 1 {
+            return 1.0;
+        }
+        let new_artist = added.is_some_and(|s| self.artist_count(s.artist) == 0);
+        (self.distinct_artists + usize::from(new_artist)) as f32 / len as f32
+    }
+
+    fn bpm_smoothness(&self, added: Option<&SongFeatures>, len: usize) -> f32 {
+        if len <= 1 {
+            return 1.0;
+        }
+        let mut jump_sum = self.bpm_jump_sum;
+        let mut jump_count = self.bpm_jump_count;
+        if let Some(added) = added
+            && self.len > 0
+            && let Some(jump) = Self::bpm_jump(self.last_bpm, added.bpm)
+        {
+            jump_sum += jump as u64;
+            jump_count += 1;
+        }
+
+        if jump_count == 0 {
+            return 0.5; // Neutral when no BPM data available
+        }
+        PlaylistScoring::bpm_smoothness_for_average_jump(jump_sum as f32 / jump_count as f32)
+    }
+
+    fn genre_count(&self, genre: usize) -> usize {
+        self.genre_counts.get(genre).copied().unwrap_or(0)
+    }
+
+    fn artist_count(&self, artist: usize) -> usize {
+        self.artist_counts.get(artist).copied().unwrap_or(0)
+    }
+
+    fn bpm_jump(previous: Option<u32>, next: Option<u32>) -> Option<u32> {
+        Some((previous? as i32 - next? as i32).unsigned_abs())
+    }
+
+    fn extend_year_range(range: Option<(u32, u32)>, year: Option<u32>) -> Option<(u32, u32)> {
+        match (range, year) {
+            (Some((min_year, max_year)), Some(year)) => {
+                Some((min_year.min(year), max_year.max(year)))
+            }
+            (None, Some(year)) => Some((year, year)),
+            (range, None) => range,
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::models::Song;
+use crate::playlist::config::DuplicatePolicy;
 use std::collections::HashMap;
 
 /// Represents a song in a playlist with additional metadata from the generation process
@@ -10,6 +11,15 @@ pub struct PlaylistSong {
 }
 
 impl PlaylistSong {
+    /// A song placed without generation scores, e.g. carried over from a previous run
+    pub fn new(song: Song) -> Self {
+        Self {
+            song,
+            transition_score: None,
+            quality_contribution: None,
+        }
+    }
+
     pub fn with_metadata(
         song: Song,
         transition_score: f32,
@@ -31,6 +41,10 @@ pub struct Playlist {
     pub quality_score: f32,
     pub metadata: PlaylistMetadata,
     pub base_name_pattern: String,
+    pub comment: String,      // Rendered from the config's comment template
+    pub public: Option<bool>, // Visibility requested by the config
+    pub duplicates: DuplicatePolicy, // Cleanup policy for older playlists from the same config
+    pub keep_history: Option<usize>, // Number of archived generations to keep
 }
 
 /// Metadata about the playlist composition
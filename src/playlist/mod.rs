@@ -1,11 +1,16 @@
 pub mod config;
+pub mod diff;
 pub mod filters;
 pub mod generator;
+pub mod incremental;
 pub mod metadata;
+pub mod optimizer;
 pub mod scoring;
+pub mod selection;
 pub mod transitions;
 pub mod utils;
 
 pub use config::*;
+pub use diff::{MovedSong, PlaylistDiff};
 pub use generator::PlaylistGenerator;
 pub use metadata::*;
@@ -0,0 +1,218 @@
+use super::config::{OptimizationSettings, PlaylistConfig};
+use super::incremental::IncrementalQuality;
+use super::transitions::PlaylistTransitions;
+use crate::models::Song;
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Global second pass over a greedily generated playlist. The greedy generator never
+/// revisits a pick, so an early choice can force bad transitions later; this local
+/// search swaps pairs of songs, reverses segments (2-opt) and swaps in unused
+/// candidates, keeping any change that raises the playlist's score without breaking
+/// more hard constraints.
+pub struct PlaylistOptimizer<'a> {
+    config: &'a PlaylistConfig,
+    settings: &'a OptimizationSettings,
+    /// Reference time for the play-recency rule
+    now: DateTime<Utc>,
+}
+
+/// Result of an optimization pass
+#[derive(Debug)]
+pub struct Optimized {
+    pub songs: Vec<Song>,
+    pub score_before: f32,
+    pub score_after: f32,
+    /// Changes that were kept
+    pub moves: usize,
+    /// Whether the time budget ran out before the search converged
+    pub timed_out: bool,
+}
+
+/// Hard constraint violations first (fewer is better), then the score
+#[derive(Debug, Clone, Copy)]
+struct Evaluation {
+    violations: usize,
+    score: f32,
+}
+
+impl Evaluation {
+    fn is_better_than(&self, other: &Evaluation) -> bool {
+        self.violations < other.violations
+            || (self.violations == other.violations && self.score > other.score + 1e-6)
+    }
+}
+
+impl<'a> PlaylistOptimizer<'a> {
+    /// An optimizer that judges play recency as of `now`
+    pub fn new(
+        config: &'a PlaylistConfig,
+        settings: &'a OptimizationSettings,
+        now: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            config,
+            settings,
+            now,
+        }
+    }
+
+    /// Improve the order of `songs` until no move helps or the time budget runs out.
+    /// Songs may be replaced by `candidates` that are not in the playlist yet and were
+    /// not played too recently; pass an empty slice to only reorder.
+    pub fn optimize(&self, songs: Vec<Song>, candidates: &[Song]) -> Optimized {
+        let deadline = Instant::now() + Duration::from_millis(self.settings.time_budget_ms);
+        let mut songs = songs;
+        let mut current = self.evaluate(&songs);
+        let score_before = current.score;
+        let mut moves = 0;
+        let mut timed_out = false;
+
+        // Replacements must pass the play-recency rule the greedy pass checks on every pick
+        let in_playlist: HashSet<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+        let mut replacements: Vec<Song> = candidates
+            .iter()
+            .filter(|song| !in_playlist.contains(song.id.as_str()))
+            .filter(|song| {
+                !self
+                    .config
+                    .min_days_since_last_play
+                    .is_some_and(|min_days| {
+                        PlaylistTransitions::would_violate_min_days_since_last_play(
+                            min_days, song, self.now,
+                        )
+                    })
+            })
+            .take(self.settings.replacement_candidates)
+            .cloned()
+            .collect();
+
+        'search: loop {
+            let mut improved = false;
+
+            for i in 0..songs.len() {
+                for j in i + 1..songs.len() {
+                    if Instant::now() >= deadline {
+                        timed_out = true;
+                        break 'search;
+                    }
+
+                    songs.swap(i, j);
+                    let swapped = self.evaluate(&songs);
+                    if swapped.is_better_than(&current) {
+                        current = swapped;
+                        moves += 1;
+                        improved = true;
+                        continue;
+                    }
+                    songs.swap(i, j);
+
+                    // Reversing two neighbours is the same as swapping them
+                    if j > i + 1 {
+                        songs[i..=j].reverse();
+                        let reversed = self.evaluate(&songs);
+                        if reversed.is_better_than(&current) {
+                            current = reversed;
+                            moves += 1;
+                            improved = true;
+                            continue;
+                        }
+                        songs[i..=j].reverse();
+                    }
+                }
+            }
+
+            for i in 0..songs.len() {
+                for replacement in replacements.iter_mut() {
+                    if Instant::now() >= deadline {
+                        timed_out = true;
+                        break 'search;
+                    }
+
+                    std::mem::swap(&mut songs[i], replacement);
+                    let replaced = self.evaluate(&songs);
+                    if replaced.is_better_than(&current) {
+                        // The dropped song stays available as a replacement
+                        current = replaced;
+                        moves += 1;
+                        improved = true;
+                    } else {
+                        std::mem::swap(&mut songs[i], replacement);
+                    }
+                }
+            }
+
+            if !improved {
+                break;
+            }
+        }
+
+        Optimized {
+            songs,
+            score_before,
+            score_after: current.score,
+            moves,
+            timed_out,
+        }
+    }
+
+    /// Playlist quality and average transition score, weighted like the greedy
+    /// generator's choices (70/30)
+    pub fn score(&self, songs: &[Song]) -> f32 {
+        if songs.is_empty() {
+            return 0.0;
+        }
+        let mut quality = IncrementalQuality::new(self.config);
+        let mut transitions = 0.0;
+        for (i, song) in songs.iter().enumerate() {
+            let features = quality.features(song);
+            if i > 0 {
+                transitions += quality.transition_score(Some(&songs[i - 1]), song, &features);
+            }
+            quality.push(&features);
+        }
+        let average_transition = if songs.len() > 1 {
+            transitions / (songs.len() - 1) as f32
+        } else {
+            0.5
+        };
+
+        quality.score() * 0.7 + average_transition * 0.3
+    }
+
+    /// Number of songs that break the artist, album or BPM jump rules where they stand
+    pub fn violations(&self, songs: &[Song]) -> usize {
+        let rules = &self.config.transition_rules;
+        (1..songs.len())
+            .filter(|&i| {
+                let previous = &songs[..i];
+                let song = &songs[i];
+                let bpm_jump = match (songs[i - 1].bpm, song.bpm) {
+                    (Some(bpm_a), Some(bpm_b)) => {
+                        (bpm_a as i32 - bpm_b as i32).unsigned_abs() > rules.max_bpm_jump
+                    }
+                    _ => false,
+                };
+                bpm_jump
+                    || PlaylistTransitions::would_violate_artist_repetition(
+                        rules.avoid_artist_repeats_within,
+                        previous,
+                        song,
+                    )
+                    || PlaylistTransitions::would_violate_album_repetition(
+                        rules.avoid_album_repeats_within,
+                        previous,
+                        song,
+                    )
+            })
+            .count()
+    }
+
+    fn evaluate(&self, songs: &[Song]) -> Evaluation {
+        Evaluation {
+            violations: self.violations(songs),
+            score: self.score(songs),
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use super::{PlaylistConfig, PlaylistMetadata};
 use crate::models::Song;
+use chrono::{DateTime, Utc};
+use rand::Rng;
 use std::collections::HashMap;
 
 /// Scoring and calculation functionality
 pub struct PlaylistScoring;
 
 impl PlaylistScoring {
-    /// Calculate a preference score for a song based on configurable weights
-    pub fn calculate_preference_score(song: &Song, config: &PlaylistConfig) -> f32 {
+    /// Calculate a preference score for a song based on configurable weights, judging
+    /// recency as of `now`. The random part (`randomness_factor`) is added separately by
+    /// `preference_jitter`.
+    pub fn calculate_preference_score(
+        song: &Song,
+        config: &PlaylistConfig,
+        now: DateTime<Utc>,
+    ) -> f32 {
         let weights = &config.preference_weights;
         let mut score = 0.0;
 
@@ -34,28 +42,36 @@ impl PlaylistScoring {
         }
 
         // Apply recency penalty (simplified since we don't have recency_penalty_days/strength)
-        if let Some(played_str) = &song.played {
-            if let Ok(days_since_played) = Self::parse_days_since_played(played_str) {
-                // Apply recency penalty - songs played recently get lower scores
-                if days_since_played < 14.0 {
-                    // Use 7 days as default
-                    let penalty_factor = 1.0 - (days_since_played / 7.0);
-                    score -= weights.recency_penalty_weight * penalty_factor;
-                }
+        if let Some(played_str) = &song.played
+            && let Ok(days_since_played) = Self::parse_days_since_played(played_str, now)
+        {
+            // Apply recency penalty - songs played recently get lower scores
+            if days_since_played < 14.0 {
+                // Use 7 days as default
+                let penalty_factor = 1.0 - (days_since_played / 7.0);
+                score -= weights.recency_penalty_weight * penalty_factor;
             }
         }
 
-        // Add randomness factor
-        let randomness_multiplier = if weights.discovery_mode { 20 } else { 10 };
-        score += (song.id.len() % randomness_multiplier) as f32 * weights.randomness_factor;
-
         score
     }
 
-    /// Parse the last played timestamp and calculate days since played
-    pub fn parse_days_since_played(played_str: &str) -> Result<f32, Box<dyn std::error::Error>> {
-        use chrono::{DateTime, Utc};
+    /// Random variation added to a preference score, up to 10 (20 in discovery mode)
+    /// times the `randomness_factor`
+    pub fn preference_jitter<R: Rng + ?Sized>(config: &PlaylistConfig, rng: &mut R) -> f32 {
+        let weights = &config.preference_weights;
+        if weights.randomness_factor <= 0.0 {
+            return 0.0;
+        }
+        let randomness_multiplier = if weights.discovery_mode { 20.0 } else { 10.0 };
+        rng.gen_range(0.0..randomness_multiplier) * weights.randomness_factor
+    }
 
+    /// Parse the last played timestamp and calculate days since played, as of `now`
+    pub fn parse_days_since_played(
+        played_str: &str,
+        now: DateTime<Utc>,
+    ) -> Result<f32, Box<dyn std::error::Error>> {
         // Try to parse as ISO 8601 format (most common for OpenSubsonic)
         let played_time = if let Ok(dt) = DateTime::parse_from_rfc3339(played_str) {
             dt.with_timezone(&Utc)
@@ -70,7 +86,6 @@ impl PlaylistScoring {
             return Ok(0.5); // Half a day ago (will trigger recency penalty)
         };
 
-        let now = Utc::now();
         let duration = now.signed_duration_since(played_time);
         let days = duration.num_days() as f32 + (duration.num_hours() % 24) as f32 / 24.0;
 
@@ -167,8 +182,14 @@ impl PlaylistScoring {
 
         // Sum weighted scores and normalize by total weight sum
         let total_score = genre_coherence_score + popularity_balance_score + era_cohesion_score + artist_diversity_score + bpm_smoothness_score;
+        Self::normalize_quality_score(total_score, config)
+    }
+
+    /// Normalize the sum of the weighted quality components by the total weight
+    pub fn normalize_quality_score(total_score: f32, config: &PlaylistConfig) -> f32 {
+        let weights = &config.quality_weights;
         let total_weight = weights.genre_coherence + weights.popularity_balance + weights.era_cohesion + weights.artist_diversity + weights.bpm_transition_smoothness;
-        
+
         if total_weight > 0.0 {
             total_score / total_weight
         } else {
@@ -195,15 +216,24 @@ impl PlaylistScoring {
             return 1.0; // Maximum coherence - single genre
         }
 
-        // Calculate distribution entropy (higher = more diverse)
+        // Calculate distribution entropy (higher = more diverse), summing in a fixed
+        // order so seeded runs reproduce exactly
+        let mut counts: Vec<usize> = genre_distribution.values().copied().collect();
+        counts.sort_unstable();
         let mut entropy = 0.0;
-        for &count in genre_distribution.values() {
+        for count in counts {
             let probability = count as f32 / total_songs as f32;
             if probability > 0.0 {
                 entropy -= probability * probability.log2();
             }
         }
 
+        Self::genre_coherence_for_entropy(entropy, genre_count)
+    }
+
+    /// Genre coherence from the entropy of the genre distribution and the number of
+    /// distinct genres (at least two)
+    pub fn genre_coherence_for_entropy(entropy: f32, genre_count: usize) -> f32 {
         // Normalize entropy based on genre count
         let max_entropy = (genre_count as f32).log2();
         let normalized_entropy = if max_entropy > 0.0 {
@@ -265,6 +295,12 @@ impl PlaylistScoring {
             .sum::<f32>()
             / play_counts.len() as f32;
 
+        Self::popularity_balance_for_moments(mean, variance)
+    }
+
+    /// Popularity balance from the mean and variance of the play counts (of at least
+    /// two songs)
+    pub fn popularity_balance_for_moments(mean: f32, variance: f32) -> f32 {
         let std_dev = variance.sqrt();
 
         // Calculate how balanced the popularity distribution is
@@ -312,7 +348,7 @@ impl PlaylistScoring {
             .windows(2)
             .filter_map(|pair| {
                 if let (Some(bpm1), Some(bpm2)) = (pair[0].bpm, pair[1].bpm) {
-                    Some((bpm1 as i32 - bpm2 as i32).abs() as u32)
+                    Some((bpm1 as i32 - bpm2 as i32).unsigned_abs())
                 } else {
                     None
                 }
@@ -325,7 +361,11 @@ impl PlaylistScoring {
 
         // Calculate average BPM jump
         let avg_jump = bpm_transitions.iter().sum::<u32>() as f32 / bpm_transitions.len() as f32;
+        Self::bpm_smoothness_for_average_jump(avg_jump)
+    }
 
+    /// BPM smoothness from the average BPM jump between neighbouring songs
+    pub fn bpm_smoothness_for_average_jump(avg_jump: f32) -> f32 {
         // Return smoothness score: 1.0 = very smooth (small jumps), 0.0 = very jarring (large jumps)
         // Use a reasonable scale where 30 BPM jump = 0.5 score
         let smoothness = (60.0 - avg_jump) / 60.0;
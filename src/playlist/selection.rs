@@ -0,0 +1,83 @@
+use super::config::SelectionSettings;
+use rand::Rng;
+
+/// Chooses the next song among the candidates that passed the hard constraints
+pub struct CandidateSelection;
+
+impl CandidateSelection {
+    /// Pick one of the candidates' combined scores and return its index. Without
+    /// settings (or with a temperature of 0) this is the first highest score;
+    /// otherwise the candidate is drawn with softmax probabilities, after cutting the
+    /// list down to `top_k` and then `top_p`.
+    pub fn choose<R: Rng + ?Sized>(
+        scores: &[f32],
+        settings: Option<&SelectionSettings>,
+        rng: &mut R,
+    ) -> Option<usize> {
+        match settings {
+            Some(settings) if settings.temperature > 0.0 && !scores.is_empty() => {
+                Some(Self::sample(scores, settings, rng))
+            }
+            _ => Self::best(scores),
+        }
+    }
+
+    /// Index of the first highest score
+    fn best(scores: &[f32]) -> Option<usize> {
+        let mut best: Option<usize> = None;
+        for (i, &score) in scores.iter().enumerate() {
+            if best.is_none_or(|b| score > scores[b]) {
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    fn sample<R: Rng + ?Sized>(scores: &[f32], settings: &SelectionSettings, rng: &mut R) -> usize {
+        // Best first; the stable sort keeps earlier candidates first among equal scores
+        let mut order: Vec<usize> = (0..scores.len()).collect();
+        order.sort_by(|&a, &b| {
+            scores[b]
+                .partial_cmp(&scores[a])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        if let Some(top_k) = settings.top_k {
+            order.truncate(top_k.max(1));
+        }
+
+        // Softmax relative to the best score, so large scores cannot overflow
+        let max_score = scores[order[0]];
+        let mut weights: Vec<f32> = order
+            .iter()
+            .map(|&i| ((scores[i] - max_score) / settings.temperature).exp())
+            .map(|weight| if weight.is_finite() { weight } else { 0.0 })
+            .collect();
+
+        if let Some(top_p) = settings.top_p {
+            let total: f32 = weights.iter().sum();
+            let mut cumulative = 0.0;
+            let mut keep = weights.len();
+            for (n, weight) in weights.iter().enumerate() {
+                cumulative += weight / total;
+                if cumulative >= top_p {
+                    keep = n + 1;
+                    break;
+                }
+            }
+            weights.truncate(keep);
+        }
+
+        let total: f32 = weights.iter().sum();
+        if total <= 0.0 || !total.is_finite() {
+            return order[0];
+        }
+        let mut target = rng.gen_range(0.0..total);
+        for (n, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return order[n];
+            }
+            target -= weight;
+        }
+        order[weights.len() - 1]
+    }
+}
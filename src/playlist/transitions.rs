@@ -1,6 +1,7 @@
 use crate::models::Song;
 use super::config::PlaylistConfig;
 use super::scoring::PlaylistScoring;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 /// Handles transition scoring and rules between songs in a playlist
@@ -98,7 +99,6 @@ impl PlaylistTransitions {
         }
 
         // Check if any genres match and find the highest frequency match
-        let coherence_pref = config.quality_weights.genre_coherence;
         let mut best_frequency = 0.0;
         let mut has_matching_genre = false;
 
@@ -111,7 +111,14 @@ impl PlaylistTransitions {
             }
         }
 
-        if has_matching_genre {
+        Self::genre_compatibility_for_frequency(config, has_matching_genre.then_some(best_frequency))
+    }
+
+    /// Genre compatibility from the share of the playlist having the candidate's most
+    /// frequent genre, or `None` if the candidate shares no genre with the playlist
+    pub fn genre_compatibility_for_frequency(config: &PlaylistConfig, best_frequency: Option<f32>) -> f32 {
+        let coherence_pref = config.quality_weights.genre_coherence;
+        if let Some(best_frequency) = best_frequency {
             // Song shares at least one genre - score based on the best frequency match
             // Higher coherence preference = higher reward for matching genres (0.5-1.0 range)
             0.5 + (best_frequency * 0.5 * coherence_pref)
@@ -164,15 +171,17 @@ impl PlaylistTransitions {
         false // No album repetition found
     }
 
-    /// Check if a candidate would violate the minimum days since last play rule
+    /// Check if a candidate would violate the minimum days since last play rule, as of `now`
     pub fn would_violate_min_days_since_last_play(
         min_days: u32,
         candidate: &Song,
+        now: DateTime<Utc>,
     ) -> bool {
-        if let Some(played_str) = &candidate.played {
-            if let Ok(days_since_played) = PlaylistScoring::parse_days_since_played(played_str) {
-                return days_since_played < min_days as f32;
-            }
+        if let Some(played_str) = &candidate.played
+            && let Ok(days_since_played) =
+                PlaylistScoring::parse_days_since_played(played_str, now)
+        {
+            return days_since_played < min_days as f32;
         }
         false // Assume no violation if no play data is available
     }
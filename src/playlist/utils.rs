@@ -1,4 +1,5 @@
-use chrono::Local;
+use chrono::{DateTime, Local, Utc};
+use rand::Rng;
 use rand::seq::SliceRandom;
 
 /// Helper trait for string formatting
@@ -27,13 +28,16 @@ impl ToTitleCase for str {
 pub struct PlaylistNaming;
 
 impl PlaylistNaming {
-    /// Generate a descriptive name for the playlist based on metadata
-    pub fn generate_playlist_name(
+    /// Generate a descriptive name for the playlist based on metadata and the local
+    /// weekday of `now`, picking the descriptive words with `rng`
+    pub fn generate_playlist_name<R: Rng + ?Sized>(
         name: String,
         metadata: &crate::playlist::PlaylistMetadata,
+        now: DateTime<Utc>,
+        rng: &mut R,
     ) -> String {
-        let day_of_week = Local::now().format("%A").to_string();
-        let suffix = Self::generate_suffix(metadata);
+        let day_of_week = now.with_timezone(&Local).format("%A").to_string();
+        let suffix = Self::generate_suffix(metadata, rng);
         format!("{name} {day_of_week} {suffix}").to_lowercase()
     }
 
@@ -41,8 +45,12 @@ impl PlaylistNaming {
     /// - tempo (based on average BPM)
     /// - era (based on era_span)
     /// - genre (if strong enough presence)
+    ///
     /// The function composes up to 2 descriptors + a noun.
-    fn generate_suffix(metadata: &crate::playlist::PlaylistMetadata) -> String {
+    fn generate_suffix<R: Rng + ?Sized>(
+        metadata: &crate::playlist::PlaylistMetadata,
+        rng: &mut R,
+    ) -> String {
         // expanded pools
         const NOUNS: &[&str] = &[
             "tunes",
@@ -71,10 +79,13 @@ impl PlaylistNaming {
             "fusion",
         ];
 
-        let mut rng = rand::thread_rng();
-
         // genre descriptor - include if it has a strong presence (>35%)
-        let genre = if let Some((genre, &count)) = metadata.genre_distribution.iter().max_by_key(|(_, c)| *c) {
+        // (ties go to the alphabetically first genre, so seeded runs reproduce exactly)
+        let genre = if let Some((genre, &count)) = metadata
+            .genre_distribution
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+        {
             if metadata.total_songs > 0 && (count as f32 / metadata.total_songs as f32) >= 0.35 {
                 Some(genre.to_lowercase())
             } else {
@@ -88,15 +99,15 @@ impl PlaylistNaming {
         let tempo = if metadata.average_bpm > 130.0 {
             // high energy options
             let options = ["energetic", "upbeat", "pumped", "lively"];
-            options.choose(&mut rng).copied()
+            options.choose(rng).copied()
         } else if metadata.average_bpm >= 100.0 {
             // mid-tempo options
             let options = ["steady", "cruising", "flowing", "smooth"];
-            options.choose(&mut rng).copied()
+            options.choose(rng).copied()
         } else if metadata.average_bpm > 0.0 {
             // low tempo/chill options
             let options = ["mellow", "relaxed", "laid-back", "easy"];
-            options.choose(&mut rng).copied()
+            options.choose(rng).copied()
         } else {
             None
         };
@@ -161,12 +172,12 @@ impl PlaylistNaming {
         }
 
         // Shuffle and pick up to 2 descriptors
-        tokens.shuffle(&mut rng);
+        tokens.shuffle(rng);
         let descriptors_count = tokens.len().min(2);
         let descriptors: Vec<String> = tokens.into_iter().take(descriptors_count).collect();
 
         // Pick a noun
-        let noun = NOUNS.choose(&mut rng).unwrap();
+        let noun = NOUNS.choose(rng).unwrap();
 
         // Build the suffix
         if descriptors.is_empty() {
@@ -176,3 +187,50 @@ impl PlaylistNaming {
         }
     }
 }
+
+/// Playlist comment rendering
+pub struct PlaylistComment;
+
+impl PlaylistComment {
+    /// Summary used when a config does not set its own comment
+    pub const DEFAULT_TEMPLATE: &'static str = "Generated by playlist-generator from '{name}' on {date}. Quality {quality}/100, avg {avg_bpm} BPM. Top genres: {genres}.";
+
+    /// Fill in a comment template. Supported placeholders: `{name}` (config name),
+    /// `{date}` (local date of `now`), `{quality}`, `{avg_bpm}`, `{genres}` (top three),
+    /// `{songs}` and `{duration}`.
+    pub fn render(
+        template: &str,
+        config_name: &str,
+        metadata: &crate::playlist::PlaylistMetadata,
+        quality_score: f32,
+        now: DateTime<Utc>,
+    ) -> String {
+        let genres = Self::top_genres(metadata, 3);
+        let genres = if genres.is_empty() {
+            "none".to_string()
+        } else {
+            genres.join(", ")
+        };
+        let date = now.with_timezone(&Local).format("%Y-%m-%d").to_string();
+
+        template
+            .replace("{name}", config_name)
+            .replace("{date}", &date)
+            .replace("{quality}", &format!("{:.0}", quality_score * 100.0))
+            .replace("{avg_bpm}", &format!("{:.0}", metadata.average_bpm))
+            .replace("{genres}", &genres)
+            .replace("{songs}", &metadata.total_songs.to_string())
+            .replace("{duration}", &format!("{}m", metadata.total_duration / 60))
+    }
+
+    /// Most common genres, most frequent first (ties broken alphabetically)
+    fn top_genres(metadata: &crate::playlist::PlaylistMetadata, count: usize) -> Vec<String> {
+        let mut genres: Vec<(&String, &usize)> = metadata.genre_distribution.iter().collect();
+        genres.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        genres
+            .into_iter()
+            .take(count)
+            .map(|(genre, _)| genre.to_title_case())
+            .collect()
+    }
+}
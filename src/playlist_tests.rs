@@ -31,6 +31,15 @@ mod tests {
         }
     }
 
+    /// A song with its own ID, artist and album, so repetition rules never hold it back
+    fn create_unique_song(id: &str) -> Song {
+        let mut song = create_test_song(&format!("Song {id}"), Some(200));
+        song.id = id.to_string();
+        song.artist = format!("Artist {id}");
+        song.album = format!("Album {id}");
+        song
+    }
+
     #[test]
     fn test_filters_interludes() {
         let interlude = create_test_song("Interlude", Some(60));
@@ -95,7 +104,7 @@ mod tests {
     #[test]
     fn test_discovery_mode_scoring() {
         use crate::playlist::scoring::PlaylistScoring;
-        use crate::playlist::{PlaylistConfig, PreferenceWeights, QualityWeights, TransitionRules};
+        use crate::playlist::{DuplicatePolicy, PlaylistConfig, PreferenceWeights, QualityWeights, TransitionRules};
 
         // Create discovery mode config
         let discovery_config = PlaylistConfig {
@@ -127,6 +136,18 @@ mod tests {
             },
             target_length: Some(20),
             min_days_since_last_play: None,
+            public: None,
+            comment: None,
+            duplicates: DuplicatePolicy::default(),
+            keep_history: None,
+            refresh: None,
+            priority: 0,
+            allow_shared_songs: false,
+            min_days_since_last_generated: None,
+            recently_generated_penalty: None,
+            rotation: None,
+            selection: None,
+            optimize: None,
         };
 
         // Create normal mode config
@@ -150,12 +171,13 @@ mod tests {
         high_played_song.play_count = Some(50);
 
         // Test discovery mode - lower play counts should score higher
+        let now = chrono::Utc::now();
         let unplayed_discovery_score =
-            PlaylistScoring::calculate_preference_score(&unplayed_song, &discovery_config);
+            PlaylistScoring::calculate_preference_score(&unplayed_song, &discovery_config, now);
         let low_discovery_score =
-            PlaylistScoring::calculate_preference_score(&low_played_song, &discovery_config);
+            PlaylistScoring::calculate_preference_score(&low_played_song, &discovery_config, now);
         let high_discovery_score =
-            PlaylistScoring::calculate_preference_score(&high_played_song, &discovery_config);
+            PlaylistScoring::calculate_preference_score(&high_played_song, &discovery_config, now);
 
         assert!(
             unplayed_discovery_score > low_discovery_score,
@@ -168,11 +190,11 @@ mod tests {
 
         // Test normal mode - higher play counts should score higher
         let unplayed_normal_score =
-            PlaylistScoring::calculate_preference_score(&unplayed_song, &normal_config);
+            PlaylistScoring::calculate_preference_score(&unplayed_song, &normal_config, now);
         let low_normal_score =
-            PlaylistScoring::calculate_preference_score(&low_played_song, &normal_config);
+            PlaylistScoring::calculate_preference_score(&low_played_song, &normal_config, now);
         let high_normal_score =
-            PlaylistScoring::calculate_preference_score(&high_played_song, &normal_config);
+            PlaylistScoring::calculate_preference_score(&high_played_song, &normal_config, now);
 
         assert!(
             high_normal_score > low_normal_score,
@@ -187,7 +209,7 @@ mod tests {
     #[test]
     fn test_release_year_filtering() {
         use crate::playlist::filters::SongFilters;
-        use crate::playlist::{PlaylistConfig, QualityWeights, TransitionRules, PreferenceWeights};
+        use crate::playlist::{DuplicatePolicy, PlaylistConfig, QualityWeights, TransitionRules, PreferenceWeights};
 
         // Base config
         let mut config = PlaylistConfig {
@@ -219,6 +241,18 @@ mod tests {
             },
             target_length: Some(20),
             min_days_since_last_play: None,
+            public: None,
+            comment: None,
+            duplicates: DuplicatePolicy::default(),
+            keep_history: None,
+            refresh: None,
+            priority: 0,
+            allow_shared_songs: false,
+            min_days_since_last_generated: None,
+            recently_generated_penalty: None,
+            rotation: None,
+            selection: None,
+            optimize: None,
         };
 
         let mut in_range = create_test_song("In Range", Some(180));
@@ -241,4 +275,467 @@ mod tests {
             assert!(SongFilters::matches_release_year(s, &config));
         }
     }
+
+    #[test]
+    fn test_comment_template_rendering() {
+        use crate::playlist::scoring::PlaylistScoring;
+        use crate::playlist::utils::PlaylistComment;
+
+        let mut songs = Vec::new();
+        for (title, genre, bpm) in [
+            ("One", "jazz", 90),
+            ("Two", "jazz", 100),
+            ("Three", "soul", 110),
+        ] {
+            let mut song = create_test_song(title, Some(180));
+            song.genre = Some(genre.to_string());
+            song.bpm = Some(bpm);
+            songs.push(song);
+        }
+        let metadata = PlaylistScoring::calculate_metadata(&songs);
+        let now = chrono::DateTime::parse_from_rfc3339("2024-03-05T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        let comment = PlaylistComment::render(
+            "{name}: {songs} songs, {avg_bpm} BPM, quality {quality}, {genres}",
+            "Evening",
+            &metadata,
+            0.756,
+            now,
+        );
+        assert_eq!(comment, "Evening: 3 songs, 100 BPM, quality 76, Jazz, Soul");
+
+        let default = PlaylistComment::render(
+            PlaylistComment::DEFAULT_TEMPLATE,
+            "Evening",
+            &metadata,
+            0.756,
+            now,
+        );
+        assert!(default.contains("'Evening'"));
+        // The date comes from the reference time, not the clock
+        let date = now
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d")
+            .to_string();
+        assert!(default.contains(&format!("on {date}.")));
+        assert!(!default.contains('{'));
+    }
+
+    #[test]
+    fn test_playlist_diff() {
+        use crate::playlist::PlaylistDiff;
+
+        let ids = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let previous = ids(&["a", "b", "c", "d"]);
+        let new = ids(&["c", "a", "e", "b", "f"]);
+
+        let diff = PlaylistDiff::between(&previous, &new);
+        assert_eq!(diff.kept, ids(&["c", "a", "b"]));
+        assert_eq!(diff.added, ids(&["e", "f"]));
+        assert_eq!(diff.removed, ids(&["d"]));
+        // Positions count only kept songs: a,b,c became c,a,b
+        let moved: Vec<(&str, usize, usize)> = diff
+            .moved
+            .iter()
+            .map(|m| (m.id.as_str(), m.from, m.to))
+            .collect();
+        assert_eq!(moved, vec![("c", 2, 0), ("a", 0, 1), ("b", 1, 2)]);
+        assert!((diff.turnover() - 0.4).abs() < 1e-6);
+        assert!(!diff.is_unchanged());
+
+        // Additions and removals alone do not count as moves
+        let diff = PlaylistDiff::between(&ids(&["a", "x", "b"]), &ids(&["a", "b", "y"]));
+        assert!(diff.moved.is_empty());
+        assert!(PlaylistDiff::between(&previous, &previous).is_unchanged());
+    }
+
+    #[test]
+    fn test_refresh_keeps_part_of_previous_playlist() {
+        use crate::playlist::{PlaylistConfig, PlaylistGenerator, RefreshSettings};
+
+        let song = |id: &str, genre: &str| {
+            let mut song = create_unique_song(id);
+            song.genre = Some(genre.to_string());
+            song
+        };
+        let previous = vec![
+            song("p0", "Jazz"),
+            song("p1", "Jazz"),
+            song("p2", "Jazz"),
+            song("p3", "Rock"), // no longer matches the config
+        ];
+        let mut library: Vec<Song> = (0..10).map(|i| song(&format!("n{i}"), "Jazz")).collect();
+        library.extend(previous.iter().cloned());
+
+        let config = PlaylistConfig {
+            acceptable_genres: Some(vec!["Jazz".to_string()]),
+            refresh: Some(RefreshSettings {
+                replace_fraction: 0.5,
+            }),
+            ..PlaylistConfig::default()
+        };
+        let playlist = PlaylistGenerator::new(config).refresh_playlist(
+            library,
+            &previous,
+            Some("Refreshed".to_string()),
+            Some(4),
+        );
+
+        let ids: Vec<&str> = playlist.songs.iter().map(|ps| ps.song.id.as_str()).collect();
+        assert_eq!(ids.len(), 4);
+        assert_eq!(ids.iter().filter(|id| id.starts_with('p')).count(), 2);
+        assert!(!ids.contains(&"p3"));
+    }
+
+    #[test]
+    fn test_refresh_orders_kept_songs_by_transition_rules() {
+        use crate::playlist::optimizer::PlaylistOptimizer;
+        use crate::playlist::{
+            OptimizationSettings, PlaylistConfig, PlaylistGenerator, RefreshSettings,
+        };
+
+        let now = chrono::Utc::now();
+        let yesterday = (now - chrono::Duration::days(1)).to_rfc3339();
+        let kept = |id: &str, artist: &str| {
+            let mut song = create_unique_song(id);
+            song.artist = artist.to_string();
+            song.played = Some(yesterday.clone());
+            song
+        };
+        let config = PlaylistConfig {
+            min_days_since_last_play: Some(7),
+            refresh: Some(RefreshSettings {
+                replace_fraction: 0.5,
+            }),
+            ..PlaylistConfig::default()
+        };
+        let settings = OptimizationSettings::default();
+        let optimizer = PlaylistOptimizer::new(&config, &settings, now);
+        let refresh = |previous: &[Song], library: Vec<Song>, target: usize| {
+            let playlist = PlaylistGenerator::new(config.clone())
+                .with_reference_time(now)
+                .refresh_playlist(
+                    library,
+                    previous,
+                    Some("Refreshed".to_string()),
+                    Some(target),
+                );
+            playlist
+                .songs
+                .into_iter()
+                .map(|ps| ps.song)
+                .collect::<Vec<_>>()
+        };
+
+        // Recently played kept songs are not rejected while reordering, so
+        // two by the same artist end up apart rather than appended together
+        let previous = vec![
+            kept("p0", "Shared"),
+            kept("p1", "Shared"),
+            kept("p2", "Solo"),
+        ];
+        let mut library: Vec<Song> = (0..10)
+            .map(|i| create_unique_song(&format!("n{i}")))
+            .collect();
+        library.extend(previous.iter().cloned());
+        let songs = refresh(&previous, library, 6);
+        assert_eq!(songs.len(), 6);
+        assert_eq!(songs.iter().filter(|s| s.id.starts_with('p')).count(), 3);
+        assert_eq!(optimizer.violations(&songs), 0);
+
+        // A kept song that cannot be placed anywhere is swapped for an unused candidate
+        let previous: Vec<Song> = (0..4).map(|i| kept(&format!("p{i}"), "Shared")).collect();
+        let mut library: Vec<Song> = (0..3)
+            .map(|i| create_unique_song(&format!("n{i}")))
+            .collect();
+        library.extend(previous.iter().cloned());
+        let songs = refresh(&previous, library, 4);
+        assert_eq!(songs.len(), 4);
+        assert_eq!(songs.iter().filter(|s| s.id.starts_with('p')).count(), 1);
+        assert_eq!(optimizer.violations(&songs), 0);
+    }
+
+    #[test]
+    fn test_recently_generated_songs_are_penalized_or_excluded() {
+        use crate::playlist::{PlaylistConfig, PlaylistGenerator};
+        use std::collections::HashMap;
+
+        let library: Vec<Song> = ["r0", "r1", "f0", "f1"]
+            .into_iter()
+            .map(create_unique_song)
+            .collect();
+        // r0 and r1 were published yesterday
+        let days_since_generated: HashMap<String, f32> =
+            [("r0".to_string(), 1.0), ("r1".to_string(), 1.0)].into();
+        let generate = |config: PlaylistConfig, target: usize| {
+            let playlist = PlaylistGenerator::new(config)
+                .with_days_since_generated(days_since_generated.clone())
+                .generate_playlist(library.clone(), Some("Rotation".to_string()), Some(target));
+            playlist
+                .songs
+                .iter()
+                .map(|ps| ps.song.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // As a hard constraint the recent songs are never picked, even to fill the playlist
+        let exclude = PlaylistConfig {
+            min_days_since_last_generated: Some(3),
+            ..PlaylistConfig::default()
+        };
+        let ids = generate(exclude, 4);
+        assert_eq!(ids.len(), 2);
+        assert!(ids.iter().all(|id| id.starts_with('f')));
+
+        // As a penalty they only lose out to fresh songs
+        let penalize = PlaylistConfig {
+            min_days_since_last_generated: Some(3),
+            recently_generated_penalty: Some(1000.0),
+            ..PlaylistConfig::default()
+        };
+        let ids = generate(penalize.clone(), 2);
+        assert!(ids.iter().all(|id| id.starts_with('f')));
+        assert_eq!(generate(penalize, 4).len(), 4);
+    }
+
+    #[test]
+    fn test_placement_history_coverage() {
+        use crate::placement_history::{Coverage, PlacementHistory};
+
+        let now = chrono::Utc::now();
+        let ids = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut history = PlacementHistory::new();
+        history.record("Jazz", &ids(&["a", "b"]), now - chrono::Duration::days(40));
+        history.record("Rock", &ids(&["b"]), now - chrono::Duration::days(2));
+        history.record("Jazz", &ids(&["c"]), now);
+
+        // The most recent placement in any playlist counts
+        let surfaced = history.days_since_surfaced(now);
+        assert!((surfaced["a"] - 40.0).abs() < 0.1);
+        assert!((surfaced["b"] - 2.0).abs() < 0.1);
+        assert!(history.days_since_placed("Jazz", now)["b"] > 39.0);
+
+        // "a" is outside the period, "c" was published just now, "d" was never surfaced
+        let coverage = Coverage::measure(["a", "b", "c", "d"], &surfaced, 28);
+        assert_eq!((coverage.eligible, coverage.surfaced), (4, 2));
+        assert!((coverage.share() - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_library_snapshot_round_trip() {
+        use crate::snapshot::{LIBRARY_SNAPSHOT_VERSION, LibrarySnapshot};
+        use crate::source::MusicSource;
+
+        let mut starred = create_test_song("Starred Song", Some(200));
+        starred.id = "a".to_string();
+        starred.starred = Some("2024-05-01T10:00:00Z".to_string());
+        starred.play_count = Some(7);
+        let mut plain = create_test_song("Plain Song", None);
+        plain.id = "b".to_string();
+        plain.bpm = None;
+
+        let dir = std::env::temp_dir().join(format!("playlist-snapshot-{}", std::process::id()));
+        let path = dir.join("nested").join("library.json");
+        let snapshot = LibrarySnapshot::new(vec![starred, plain], chrono::Utc::now());
+        snapshot.save(&path).unwrap();
+
+        let loaded = LibrarySnapshot::load(&path).unwrap();
+        assert_eq!(loaded.version, LIBRARY_SNAPSHOT_VERSION);
+        assert_eq!(loaded.created_at, snapshot.created_at);
+        assert_eq!(
+            serde_json::to_value(&loaded.songs).unwrap(),
+            serde_json::to_value(&snapshot.songs).unwrap()
+        );
+        // Every song comes back in saved order, whatever count is asked for
+        let ids: Vec<String> = loaded
+            .fetch_songs(Some(1))
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, ["a", "b"]);
+
+        // Snapshots from another version are rejected
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(
+            &path,
+            content.replacen("\"version\": 1", "\"version\": 9", 1),
+        )
+        .unwrap();
+        assert!(LibrarySnapshot::load(&path).is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_candidate_selection() {
+        use crate::playlist::SelectionSettings;
+        use crate::playlist::selection::CandidateSelection;
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+        use std::collections::HashSet;
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let scores = [0.5, 0.8, 0.8, 0.2];
+        let settings =
+            |temperature: f32, top_k: Option<usize>, top_p: Option<f32>| SelectionSettings {
+                temperature,
+                top_k,
+                top_p,
+            };
+
+        // Greedy: the first of the highest scores
+        assert_eq!(CandidateSelection::choose(&scores, None, &mut rng), Some(1));
+        assert_eq!(CandidateSelection::choose(&[], None, &mut rng), None);
+
+        let mut draw = |settings: &SelectionSettings| -> HashSet<usize> {
+            (0..200)
+                .map(|_| CandidateSelection::choose(&scores, Some(settings), &mut rng).unwrap())
+                .collect()
+        };
+        assert_eq!(draw(&settings(0.0, None, None)), [1].into());
+
+        // A high temperature gives every candidate a chance
+        assert_eq!(draw(&settings(10.0, None, None)), [0, 1, 2, 3].into());
+        // ...unless the list is cut down first
+        assert_eq!(draw(&settings(10.0, Some(2), None)), [1, 2].into());
+        assert_eq!(draw(&settings(10.0, None, Some(0.01))), [1].into());
+        // A low temperature all but rules out clearly worse candidates
+        assert_eq!(draw(&settings(0.01, None, None)), [1, 2].into());
+    }
+
+    #[test]
+    fn test_optimizer_repairs_bpm_jumps() {
+        use crate::playlist::optimizer::PlaylistOptimizer;
+        use crate::playlist::{OptimizationSettings, PlaylistConfig};
+
+        let song = |id: &str, bpm: u32| {
+            let mut song = create_unique_song(id);
+            song.bpm = Some(bpm);
+            song
+        };
+        // Jumps between slow and fast songs on every step; the best order only has one
+        let songs = vec![
+            song("a", 90),
+            song("b", 150),
+            song("c", 95),
+            song("d", 145),
+            song("e", 100),
+            song("f", 140),
+        ];
+        let config = PlaylistConfig::default();
+        let settings = OptimizationSettings {
+            time_budget_ms: 10_000,
+            replacement_candidates: 0,
+        };
+        let now = chrono::Utc::now();
+        let optimizer = PlaylistOptimizer::new(&config, &settings, now);
+        assert_eq!(optimizer.violations(&songs), 5);
+
+        let optimized = optimizer.optimize(songs.clone(), &[]);
+        assert!(!optimized.timed_out);
+        assert_eq!(optimizer.violations(&optimized.songs), 1);
+        assert!(optimized.score_after >= optimized.score_before);
+        let ids = |songs: &[Song]| {
+            let mut ids: Vec<String> = songs.iter().map(|s| s.id.clone()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&optimized.songs), ids(&songs));
+
+        // Replacements can swap out a song no order can fit: two songs by one artist
+        let mut same_artist = vec![song("p", 100), song("q", 100), song("r", 100)];
+        same_artist[1].artist = same_artist[0].artist.clone();
+        let with_replacements = OptimizationSettings {
+            time_budget_ms: 10_000,
+            replacement_candidates: 5,
+        };
+        let optimizer = PlaylistOptimizer::new(&config, &with_replacements, now);
+        assert_eq!(optimizer.violations(&same_artist), 1);
+        let optimized = optimizer.optimize(same_artist, &[song("z", 100)]);
+        assert_eq!(optimizer.violations(&optimized.songs), 0);
+        assert_eq!(optimized.songs.len(), 3);
+        assert!(optimized.songs.iter().any(|s| s.id == "z"));
+
+        // ...but never with a song played more recently than the config allows
+        let recent_play_config = PlaylistConfig {
+            min_days_since_last_play: Some(7),
+            ..PlaylistConfig::default()
+        };
+        let mut played_yesterday = song("z", 100);
+        played_yesterday.played = Some((now - chrono::Duration::days(1)).to_rfc3339());
+        let mut same_artist = vec![song("p", 100), song("q", 100), song("r", 100)];
+        same_artist[1].artist = same_artist[0].artist.clone();
+        let optimizer = PlaylistOptimizer::new(&recent_play_config, &with_replacements, now);
+        let optimized = optimizer.optimize(same_artist, &[played_yesterday]);
+        assert_eq!(optimizer.violations(&optimized.songs), 1);
+        assert!(optimized.songs.iter().all(|s| s.id != "z"));
+
+        // Without any time the playlist is returned unchanged
+        let no_time = OptimizationSettings {
+            time_budget_ms: 0,
+            replacement_candidates: 0,
+        };
+        let optimized = PlaylistOptimizer::new(&config, &no_time, now).optimize(songs.clone(), &[]);
+        assert!(optimized.timed_out);
+        assert_eq!(optimized.moves, 0);
+    }
+
+    #[test]
+    fn test_incremental_quality_matches_full_scoring() {
+        use crate::models::Genre;
+        use crate::playlist::PlaylistConfig;
+        use crate::playlist::incremental::IncrementalQuality;
+        use crate::playlist::scoring::PlaylistScoring;
+        use crate::playlist::transitions::PlaylistTransitions;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let genres = ["Jazz", "jazz", "Bebop", "Soul", "Funk"];
+        let mut rng = StdRng::seed_from_u64(7);
+        let songs: Vec<Song> = (0..40)
+            .map(|i| {
+                let mut song = create_test_song(&format!("Song {i}"), Some(200));
+                song.id = i.to_string();
+                song.artist = format!("Artist {}", rng.gen_range(0..8));
+                song.genre = rng
+                    .gen_bool(0.8)
+                    .then(|| genres[rng.gen_range(0..genres.len())].to_string());
+                song.genres = rng.gen_bool(0.5).then(|| {
+                    vec![Genre {
+                        name: genres[rng.gen_range(0..genres.len())].to_string(),
+                    }]
+                });
+                song.bpm = rng.gen_bool(0.8).then(|| rng.gen_range(80..160));
+                song.year = rng.gen_bool(0.8).then(|| rng.gen_range(1950..2020));
+                song.play_count = rng.gen_bool(0.7).then(|| rng.gen_range(0..200));
+                song
+            })
+            .collect();
+
+        let mut config = PlaylistConfig::default();
+        for preferred_bpm_change in [0, 5] {
+            config.transition_rules.preferred_bpm_change = preferred_bpm_change;
+            let mut quality = IncrementalQuality::new(&config);
+            for (i, song) in songs.iter().enumerate() {
+                let features = quality.features(song);
+                let prefix = &songs[..i];
+                let with_song = &songs[..=i];
+                let metadata = PlaylistScoring::calculate_metadata(with_song);
+                let expected_quality =
+                    PlaylistScoring::calculate_quality_score(with_song, &metadata, &config);
+                let expected_transition =
+                    PlaylistTransitions::calculate_transition_score(&config, prefix, song);
+
+                assert!((quality.score_with(&features) - expected_quality).abs() < 1e-4);
+                let transition = quality.transition_score(prefix.last(), song, &features);
+                assert!((transition - expected_transition).abs() < 1e-4);
+
+                quality.push(&features);
+                assert!((quality.score() - expected_quality).abs() < 1e-4);
+            }
+            assert_eq!(quality.len(), songs.len());
+        }
+    }
 }
@@ -0,0 +1,140 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Bump this whenever the on-disk layout changes; older files are discarded
+pub const RUN_HISTORY_VERSION: u32 = 1;
+
+const RUN_HISTORY_FILE: &str = "run-history.json";
+
+/// Generations kept per config; older ones can no longer be restored with `undo`
+const MAX_GENERATIONS_PER_CONFIG: usize = 30;
+
+/// Local record of every playlist the generator published, used to undo a run
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunHistory {
+    pub version: u32,
+    pub generations: Vec<Generation>,
+}
+
+/// One published playlist and the songs it was given
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Generation {
+    /// Start of the run that published it; shared by every playlist of that run
+    pub run_at: String,
+    pub config_name: String,
+    pub playlist_id: String,
+    pub playlist_name: String,
+    pub song_ids: Vec<String>,
+}
+
+impl RunHistory {
+    /// Create an empty history with the current version
+    pub fn new() -> Self {
+        Self {
+            version: RUN_HISTORY_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Location of the history file inside the data directory
+    pub fn path(data_dir: &Path) -> PathBuf {
+        data_dir.join(RUN_HISTORY_FILE)
+    }
+
+    /// Load the history from disk, starting a new one if there is none yet or it
+    /// was written by an incompatible version
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = Self::path(data_dir);
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let history: RunHistory = serde_json::from_str(&content).map_err(|e| {
+            anyhow::anyhow!("Failed to parse run history {}: {}", path.display(), e)
+        })?;
+
+        if history.version != RUN_HISTORY_VERSION {
+            println!(
+                "Run history version {} is outdated (expected {}), starting a new one",
+                history.version, RUN_HISTORY_VERSION
+            );
+            return Ok(Self::new());
+        }
+
+        Ok(history)
+    }
+
+    /// Write the history to disk, replacing the previous file atomically
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = Self::path(data_dir);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    /// Add a generation, dropping the oldest ones of its config beyond the limit
+    pub fn record(&mut self, generation: Generation) {
+        let config_name = generation.config_name.clone();
+        self.generations.push(generation);
+
+        let count = self.generations_of(&config_name).count();
+        let mut excess = count.saturating_sub(MAX_GENERATIONS_PER_CONFIG);
+        self.generations.retain(|g| {
+            if excess > 0 && g.config_name == config_name {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Generations of one config, oldest first
+    pub fn generations_of<'a>(
+        &'a self,
+        config_name: &'a str,
+    ) -> impl Iterator<Item = &'a Generation> {
+        self.generations
+            .iter()
+            .filter(move |g| g.config_name == config_name)
+    }
+
+    /// Configs published by the most recent run
+    pub fn last_run_configs(&self) -> Vec<String> {
+        let Some(last_run) = self.generations.iter().map(|g| &g.run_at).max() else {
+            return Vec::new();
+        };
+        self.generations
+            .iter()
+            .filter(|g| &g.run_at == last_run)
+            .map(|g| g.config_name.clone())
+            .collect()
+    }
+
+    /// The latest generation of a config and the one before it, if there are two
+    pub fn last_two(&self, config_name: &str) -> Option<(&Generation, &Generation)> {
+        let mut generations = self
+            .generations
+            .iter()
+            .rev()
+            .filter(|g| g.config_name == config_name);
+        let latest = generations.next()?;
+        let previous = generations.next()?;
+        Some((latest, previous))
+    }
+
+    /// Forget the latest generation of a config, after it has been undone
+    pub fn remove_latest(&mut self, config_name: &str) {
+        if let Some(index) = self
+            .generations
+            .iter()
+            .rposition(|g| g.config_name == config_name)
+        {
+            self.generations.remove(index);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::placement_history::Coverage;
+use crate::playlist::{Playlist, PlaylistDiff};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+/// Machine-readable summary of one run, written with `--report`
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub run_at: String,
+    /// Time the playlists were generated as of; pass to `--reference-time` with the
+    /// seed to reproduce them on a later day
+    pub reference_time: String,
+    /// Debug runs generate playlists without publishing them
+    pub debug: bool,
+    /// Pass to `--seed` to reproduce the run's playlists from the same library
+    pub seed: u64,
+    pub playlists: Vec<PlaylistReport>,
+}
+
+/// What happened to one generated playlist
+#[derive(Debug, Serialize)]
+pub struct PlaylistReport {
+    pub config_name: String,
+    pub name: String,
+    pub status: PlaylistStatus,
+    /// Server ID, once the playlist was published
+    pub playlist_id: Option<String>,
+    pub error: Option<String>,
+    pub song_count: usize,
+    /// Quality score from 0 to 100
+    pub quality: f32,
+    /// With `--exclusive`: matching songs earlier playlists of the run had already taken
+    pub songs_taken_by_earlier: Option<usize>,
+    /// Share of songs that were not in the previous generation
+    pub turnover: Option<f32>,
+    /// Changes against the previous generation, if there was one
+    pub diff: Option<PlaylistDiff>,
+    /// Share of the config's eligible songs surfaced lately, counting only published playlists
+    pub coverage: Option<Coverage>,
+}
+
+impl PlaylistReport {
+    /// Record the changes against the previous generation
+    pub fn set_diff(&mut self, diff: Option<PlaylistDiff>) {
+        self.turnover = diff.as_ref().map(PlaylistDiff::turnover);
+        self.diff = diff;
+    }
+}
+
+/// Outcome of a playlist in the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistStatus {
+    Published,
+    /// Generated in debug mode and not written to the server
+    Generated,
+    /// No songs matched the config
+    Empty,
+    Failed,
+}
+
+impl RunReport {
+    pub fn new(run_at: String, reference_time: String, debug: bool, seed: u64) -> Self {
+        Self {
+            run_at,
+            reference_time,
+            debug,
+            seed,
+            playlists: Vec::new(),
+        }
+    }
+
+    /// Add a playlist's entry and return it so the caller can fill in the outcome
+    pub fn add(&mut self, playlist: &Playlist) -> &mut PlaylistReport {
+        self.playlists.push(PlaylistReport {
+            config_name: playlist.base_name_pattern.clone(),
+            name: playlist.name.clone(),
+            status: PlaylistStatus::Generated,
+            playlist_id: None,
+            error: None,
+            song_count: playlist.songs.len(),
+            quality: playlist.quality_score * 100.0,
+            songs_taken_by_earlier: None,
+            turnover: None,
+            diff: None,
+            coverage: None,
+        });
+        self.playlists.last_mut().unwrap()
+    }
+
+    /// Write the report as pretty-printed JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write run report {}", path.display()))
+    }
+}
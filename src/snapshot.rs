@@ -0,0 +1,79 @@
+use crate::models::Song;
+use crate::source::{LibraryProgress, MusicSource};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bump this whenever the snapshot layout changes
+pub const LIBRARY_SNAPSHOT_VERSION: u32 = 1;
+
+/// A frozen copy of fetched songs that can replace the server as a song source
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibrarySnapshot {
+    pub version: u32,
+    /// Reference time of the run that saved it, and of offline runs generating from it
+    pub created_at: DateTime<Utc>,
+    pub songs: Vec<Song>,
+}
+
+impl LibrarySnapshot {
+    /// Create a snapshot of the given songs, stamped with the run's reference time
+    pub fn new(songs: Vec<Song>, created_at: DateTime<Utc>) -> Self {
+        Self {
+            version: LIBRARY_SNAPSHOT_VERSION,
+            created_at,
+            songs,
+        }
+    }
+
+    /// Load a snapshot from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read library snapshot {}: {}", path.display(), e)
+        })?;
+        let snapshot: LibrarySnapshot = serde_json::from_str(&content).map_err(|e| {
+            anyhow::anyhow!("Failed to parse library snapshot {}: {}", path.display(), e)
+        })?;
+
+        if snapshot.version != LIBRARY_SNAPSHOT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Library snapshot {} has version {}, expected {}",
+                path.display(),
+                snapshot.version,
+                LIBRARY_SNAPSHOT_VERSION
+            ));
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Write the snapshot to a JSON file
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl MusicSource for LibrarySnapshot {
+    fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Snapshots always return every song, in the order they were saved, so runs are reproducible
+    fn fetch_songs(&self, _count: Option<u32>) -> Result<Vec<Song>> {
+        Ok(self.songs.clone())
+    }
+
+    fn fetch_all_songs(&self, on_progress: &mut dyn FnMut(&LibraryProgress)) -> Result<Vec<Song>> {
+        on_progress(&LibraryProgress {
+            albums_scanned: 0,
+            albums_total: 0,
+            songs_found: self.songs.len(),
+        });
+        Ok(self.songs.clone())
+    }
+}
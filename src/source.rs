@@ -0,0 +1,394 @@
+use crate::models::{PlaylistInfo, Song};
+use crate::ownership;
+use crate::playlist::{DuplicatePolicy, PlaylistDiff};
+use anyhow::{Context, Result};
+
+/// Progress report emitted while walking the full library
+#[derive(Debug, Clone, Copy)]
+pub struct LibraryProgress {
+    pub albums_scanned: usize,
+    pub albums_total: usize,
+    pub songs_found: usize,
+}
+
+/// A backend that provides the songs playlists are generated from
+pub trait MusicSource {
+    /// Check that the source is reachable
+    fn ping(&self) -> Result<()>;
+
+    /// Fetch a sample of songs. Sources that cannot sample may return everything.
+    fn fetch_songs(&self, count: Option<u32>) -> Result<Vec<Song>>;
+
+    /// Fetch every song the source knows about
+    fn fetch_all_songs(&self, on_progress: &mut dyn FnMut(&LibraryProgress)) -> Result<Vec<Song>>;
+}
+
+/// A destination that generated playlists are published to
+pub trait PlaylistSink {
+    /// List the playlists currently stored in the sink
+    fn list_playlists(&self) -> Result<Vec<PlaylistInfo>>;
+
+    /// Create a new playlist and return its ID
+    fn create_playlist(&self, name: &str, song_ids: &[String]) -> Result<String>;
+
+    /// Replace the name and contents of an existing playlist
+    fn update_playlist(&self, playlist_id: &str, name: &str, song_ids: &[String])
+    -> Result<String>;
+
+    /// The song IDs currently in a playlist, in order
+    fn playlist_song_ids(&self, playlist_id: &str) -> Result<Vec<String>>;
+
+    /// The songs currently in a playlist, with their metadata. Entries the sink cannot
+    /// describe as songs are left out.
+    fn playlist_songs(&self, playlist_id: &str) -> Result<Vec<Song>>;
+
+    /// Delete a playlist
+    fn delete_playlist(&self, playlist_id: &str) -> Result<()>;
+
+    /// Rename a playlist, keeping its songs
+    fn rename_playlist(&self, playlist_id: &str, name: &str) -> Result<()>;
+
+    /// Set the comment and visibility of a playlist. `None` leaves that field unchanged.
+    fn update_playlist_details(
+        &self,
+        playlist_id: &str,
+        comment: Option<&str>,
+        public: Option<bool>,
+    ) -> Result<()>;
+
+    /// Create a new playlist or update the one this config generated before.
+    /// Only playlists carrying the config's ownership marker are reused; others whose
+    /// name starts with the config name are left alone and reported as collisions.
+    /// When several owned playlists exist, one is kept and the rest are reconciled
+    /// according to the upload's duplicate policy. With `keep_history` the previous
+    /// generation is archived instead of overwritten, and the oldest archives pruned;
+    /// `keep_history: 0` overwrites it and prunes every archive.
+    fn create_playlist_with_pattern_cleanup(
+        &self,
+        upload: &PlaylistUpload,
+    ) -> Result<PublishOutcome> {
+        // An empty result would wipe the listener's playlist, so keep the old one instead
+        anyhow::ensure!(
+            !upload.song_ids.is_empty(),
+            "Refusing to publish '{}' without songs; the existing playlist was left unchanged",
+            upload.name
+        );
+
+        let existing_playlists = self
+            .list_playlists()
+            .context("Failed to list playlists to find the ones this tool owns")?;
+
+        let pattern = upload.config_name.to_lowercase();
+        let owned = owned_playlists(&existing_playlists, upload.config_name, upload.name);
+        // Playlists generated from other configs are not collisions, even when one config
+        // name starts with another
+        let collisions: Vec<String> = existing_playlists
+            .iter()
+            .filter(|p| !p.is_generated())
+            .filter(|p| p.name == upload.name || p.name.to_lowercase().starts_with(&pattern))
+            .map(|p| p.name.clone())
+            .collect();
+
+        for name in &collisions {
+            println!(
+                "Skipping playlist '{}': matches '{}' but was not created by playlist-generator",
+                name, upload.config_name
+            );
+        }
+
+        let previous = owned.first().copied();
+        if let Some(existing) = previous {
+            println!(
+                "Found existing playlist '{}' generated from '{}' (ID: {})",
+                existing.name, upload.config_name, existing.id
+            );
+        }
+        // With no archives to keep, archiving the previous generation would only delete it
+        // again, so `keep_history: 0` updates in place like no history at all
+        let archives_previous = upload.keep_history.is_some_and(|keep| keep > 0);
+        let (playlist_id, updated_existing, previous_ids) = match previous {
+            Some(existing) if !archives_previous => {
+                let previous_ids = self.replace_playlist(existing, upload.name, upload.song_ids)?;
+                (existing.id.clone(), true, Some(previous_ids))
+            }
+            // The previous generation is kept, so compare against it but publish a new one
+            Some(existing) => {
+                let previous_ids = self
+                    .playlist_song_ids(&existing.id)
+                    .context("Failed to read the previous generation")?;
+                let id = self.create_verified_playlist(upload.name, upload.song_ids)?;
+                (id, false, Some(previous_ids))
+            }
+            None => (
+                self.create_verified_playlist(upload.name, upload.song_ids)?,
+                false,
+                None,
+            ),
+        };
+        let diff = previous_ids.map(|ids| PlaylistDiff::between(&ids, upload.song_ids));
+
+        // Without the marker the next run would not recognise the playlist as its own
+        let comment = ownership::mark_comment(upload.comment, upload.config_name);
+        self.update_playlist_details(&playlist_id, Some(&comment), upload.public)
+            .with_context(|| {
+                format!("Playlist {playlist_id} was written but could not be marked as generated")
+            })?;
+
+        let mut archived = None;
+        let mut pruned_archives = Vec::new();
+        if let Some(keep) = upload.keep_history {
+            if archives_previous && let Some(existing) = previous {
+                let name = self
+                    .archive_playlist(existing, upload.config_name)
+                    .with_context(|| {
+                        format!(
+                            "Playlist {playlist_id} was created but the previous generation could not be archived"
+                        )
+                    })?;
+                println!("Archived previous generation as '{name}'");
+                archived = Some(name);
+            }
+
+            // Newest first: the generation archived just now, then older ones by creation
+            let mut older: Vec<&PlaylistInfo> = existing_playlists
+                .iter()
+                .filter(|p| p.is_archived_from(upload.config_name))
+                .collect();
+            older.sort_by(|a, b| b.created.cmp(&a.created));
+            let already_kept = usize::from(archived.is_some());
+            for expired in older.iter().skip(keep.saturating_sub(already_kept)) {
+                match self.delete_playlist(&expired.id) {
+                    Ok(()) => pruned_archives.push(expired.name.clone()),
+                    Err(e) => println!(
+                        "Warning: Failed to delete old archive '{}': {e:#}",
+                        expired.name
+                    ),
+                }
+            }
+        }
+
+        let duplicates = owned
+            .iter()
+            .skip(1)
+            .map(|duplicate| ReconciledDuplicate {
+                name: duplicate.name.clone(),
+                action: self.reconcile_duplicate(duplicate, upload),
+            })
+            .collect();
+
+        Ok(PublishOutcome {
+            playlist_id,
+            updated_existing,
+            collisions,
+            duplicates,
+            archived,
+            pruned_archives,
+            diff,
+        })
+    }
+
+    /// Apply the upload's duplicate policy to an extra playlist generated from the same
+    /// config. Failures are reported rather than returned, since the canonical playlist
+    /// has already been written.
+    fn reconcile_duplicate(
+        &self,
+        duplicate: &PlaylistInfo,
+        upload: &PlaylistUpload,
+    ) -> DuplicateAction {
+        let result = match upload.duplicates {
+            DuplicatePolicy::Keep => Ok(DuplicateAction::Kept),
+            DuplicatePolicy::Delete => self
+                .delete_playlist(&duplicate.id)
+                .map(|()| DuplicateAction::Deleted),
+            DuplicatePolicy::Archive => self
+                .archive_playlist(duplicate, upload.config_name)
+                .map(DuplicateAction::Archived),
+        };
+        result.unwrap_or_else(|e| DuplicateAction::Failed(format!("{e:#}")))
+    }
+
+    /// Rename a generated playlist with an "(archived <date>)" suffix and swap its
+    /// ownership marker for the archive marker. Returns the new name.
+    fn archive_playlist(&self, playlist: &PlaylistInfo, config_name: &str) -> Result<String> {
+        let date = chrono::Local::now().format("%Y-%m-%d");
+        let archived_name = format!("{} (archived {date})", playlist.name);
+        let comment =
+            ownership::mark_archived(playlist.comment.as_deref().unwrap_or_default(), config_name);
+        // Drop the ownership marker first, so a failed rename leaves an unmanaged
+        // playlist rather than a second one that looks current
+        self.update_playlist_details(&playlist.id, Some(&comment), None)?;
+        self.rename_playlist(&playlist.id, &archived_name)?;
+        Ok(archived_name)
+    }
+
+    /// The songs of the playlist a config generated last, or `None` if there is none.
+    /// Used to show what a run would change without writing anything.
+    fn previous_generation(&self, config_name: &str, name: &str) -> Result<Option<Vec<String>>> {
+        let existing_playlists = self.list_playlists()?;
+        match owned_playlists(&existing_playlists, config_name, name).first() {
+            Some(previous) => Ok(Some(self.playlist_song_ids(&previous.id)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The songs of the playlist a config generated last, or `None` if there is none.
+    /// Used as the starting point of a partial refresh.
+    fn previous_generation_songs(&self, config_name: &str) -> Result<Option<Vec<Song>>> {
+        let existing_playlists = self.list_playlists()?;
+        match owned_playlists(&existing_playlists, config_name, "").first() {
+            Some(previous) => Ok(Some(self.playlist_songs(&previous.id)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Swap the songs of an existing playlist for `song_ids` and check the result. If the
+    /// write fails or the server ends up with different songs, the previous name and
+    /// track list are put back and an error is returned. Returns the songs the playlist
+    /// held before.
+    fn replace_playlist(
+        &self,
+        existing: &PlaylistInfo,
+        name: &str,
+        song_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let previous_ids = self
+            .playlist_song_ids(&existing.id)
+            .context("Failed to read the current tracks before replacing them")?;
+
+        let problem = match self
+            .update_playlist(&existing.id, name, song_ids)
+            .and_then(|_| self.playlist_song_ids(&existing.id))
+        {
+            Ok(written) if written == song_ids => return Ok(previous_ids),
+            Ok(written) => anyhow::anyhow!(
+                "Playlist '{name}' holds {} songs after the update instead of the {} sent",
+                written.len(),
+                song_ids.len()
+            ),
+            Err(e) => e,
+        };
+
+        println!(
+            "⚠️ Replacing '{}' did not go through, restoring its previous {} songs...",
+            existing.name,
+            previous_ids.len()
+        );
+        let restored = self
+            .update_playlist(&existing.id, &existing.name, &previous_ids)
+            .and_then(|_| self.playlist_song_ids(&existing.id));
+        match restored {
+            Ok(ids) if ids == previous_ids => Err(problem.context(format!(
+                "Playlist '{}' was restored to its previous tracks",
+                existing.name
+            ))),
+            _ => Err(problem.context(format!(
+                "Playlist '{}' could not be restored and may be incomplete",
+                existing.name
+            ))),
+        }
+    }
+
+    /// Create a playlist and check that it holds exactly `song_ids`. A playlist that
+    /// does not is deleted again rather than left half-filled.
+    fn create_verified_playlist(&self, name: &str, song_ids: &[String]) -> Result<String> {
+        let playlist_id = self.create_playlist(name, song_ids)?;
+
+        let problem = match self.playlist_song_ids(&playlist_id) {
+            Ok(written) if written == song_ids => return Ok(playlist_id),
+            Ok(written) => anyhow::anyhow!(
+                "Playlist '{name}' holds {} songs after creation instead of the {} sent",
+                written.len(),
+                song_ids.len()
+            ),
+            Err(e) => e,
+        };
+
+        match self.delete_playlist(&playlist_id) {
+            Ok(()) => Err(problem.context(format!("Removed incomplete playlist '{name}'"))),
+            Err(_) => Err(problem.context(format!(
+                "Playlist '{name}' (ID: {playlist_id}) is incomplete and could not be removed"
+            ))),
+        }
+    }
+}
+
+/// The playlists generated from a config, the one to keep updating first: the one
+/// with today's name, else the most recently changed
+fn owned_playlists<'a>(
+    playlists: &'a [PlaylistInfo],
+    config_name: &str,
+    name: &str,
+) -> Vec<&'a PlaylistInfo> {
+    let mut owned: Vec<&PlaylistInfo> = playlists
+        .iter()
+        .filter(|p| p.is_owned_by(config_name))
+        .collect();
+    owned.sort_by(|a, b| {
+        (b.name == name)
+            .cmp(&(a.name == name))
+            .then_with(|| b.changed.cmp(&a.changed))
+    });
+    owned
+}
+
+/// A generated playlist, ready to be written to a sink
+#[derive(Debug, Clone, Copy)]
+pub struct PlaylistUpload<'a> {
+    pub name: &'a str,
+    /// Name of the config that generated the playlist, recorded in the ownership marker
+    pub config_name: &'a str,
+    pub song_ids: &'a [String],
+    pub comment: &'a str,
+    pub public: Option<bool>,
+    /// What to do with other playlists generated from the same config
+    pub duplicates: DuplicatePolicy,
+    /// Archive the previous generation instead of replacing it, keeping this many archives
+    pub keep_history: Option<usize>,
+}
+
+/// What publishing a playlist did on the sink
+#[derive(Debug, Clone)]
+pub struct PublishOutcome {
+    pub playlist_id: String,
+    /// Whether a previously generated playlist was reused instead of creating a new one
+    pub updated_existing: bool,
+    /// Playlists with a matching name that were skipped because the tool does not own them
+    pub collisions: Vec<String>,
+    /// Other playlists generated from the same config and what was done with them
+    pub duplicates: Vec<ReconciledDuplicate>,
+    /// New name of the previous generation, when it was archived instead of replaced
+    pub archived: Option<String>,
+    /// Archives deleted because they were older than the configured history
+    pub pruned_archives: Vec<String>,
+    /// Changes against the previous generation, if there was one
+    pub diff: Option<PlaylistDiff>,
+}
+
+/// An extra playlist generated from the same config as the one that was published
+#[derive(Debug, Clone)]
+pub struct ReconciledDuplicate {
+    pub name: String,
+    pub action: DuplicateAction,
+}
+
+/// What the duplicate policy did with an extra playlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DuplicateAction {
+    Deleted,
+    /// Renamed to the given name and no longer managed
+    Archived(String),
+    Kept,
+    /// The policy could not be applied; the playlist is unchanged or only partly archived
+    Failed(String),
+}
+
+impl std::fmt::Display for DuplicateAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DuplicateAction::Deleted => write!(f, "deleted"),
+            DuplicateAction::Archived(name) => write!(f, "archived as '{name}'"),
+            DuplicateAction::Kept => write!(f, "kept"),
+            DuplicateAction::Failed(error) => write!(f, "could not be cleaned up: {error}"),
+        }
+    }
+}
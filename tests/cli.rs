@@ -0,0 +1,695 @@
+//! End-to-end runs of the binary against the in-process mock server
+#![cfg(feature = "cli")]
+
+mod mock_server;
+
+use mock_server::{API_KEY, MockServer, PASSWORD, USERNAME, sample_library};
+use playlist_generator::ownership;
+use playlist_generator::playlist::{
+    PlaylistConfig, PreferenceWeights, RefreshSettings, RotationSettings,
+};
+use playlist_generator::snapshot::LibrarySnapshot;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A small jazz playlist that the sample library can always fill
+fn jazz_config() -> PlaylistConfig {
+    PlaylistConfig {
+        name: "Test Jazz".to_string(),
+        acceptable_genres: Some(vec!["Jazz".to_string()]),
+        target_length: Some(8),
+        ..PlaylistConfig::default()
+    }
+}
+
+/// A fresh working directory holding the jazz config
+fn work_dir(test: &str) -> PathBuf {
+    work_dir_with(test, vec![jazz_config()])
+}
+
+/// A fresh working directory holding the given playlist configs
+fn work_dir_with(test: &str, configs: Vec<PlaylistConfig>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "playlist-generator-{}-{}",
+        test,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    std::fs::write(
+        dir.join("playlists.json"),
+        serde_json::to_string(&configs).unwrap(),
+    )
+    .unwrap();
+    dir
+}
+
+/// The binary pointed at the mock server, with no environment leaking in from the host
+fn command(server: &MockServer, dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_playlist-generator"));
+    command
+        .current_dir(dir)
+        .env_clear()
+        .env("BASE_URL", &server.base_url)
+        .env("USERNAME", USERNAME)
+        .env("PASSWORD", PASSWORD)
+        .env("HTTP_RETRY_BACKOFF_MS", "1")
+        .args(["--sample-size", "300"]);
+    command
+}
+
+fn run(server: &MockServer, dir: &Path, extra_args: &[&str]) -> Output {
+    let output = command(server, dir).args(extra_args).output().unwrap();
+    assert!(
+        output.status.success(),
+        "run failed:\n{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn run_publishes_generated_playlist() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("publish");
+
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    let playlist = &state.playlists[0];
+    assert!(playlist.name.to_lowercase().starts_with("test jazz"));
+    assert_eq!(playlist.song_ids.len(), 8);
+    for id in &playlist.song_ids {
+        let song = state.songs.iter().find(|s| &s.id == id).unwrap();
+        assert_eq!(song.genre.as_deref(), Some("Jazz"));
+    }
+}
+
+#[test]
+fn second_run_updates_existing_playlist() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("rerun");
+
+    run(&server, &dir, &[]);
+    let first_id = server.state().playlists[0].id.clone();
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].id, first_id);
+    assert_eq!(state.requests_to("createPlaylist").len(), 1);
+}
+
+#[test]
+fn published_playlist_gets_default_summary_comment() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("comment");
+
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    let playlist = &state.playlists[0];
+    let comment = playlist.comment.as_deref().unwrap();
+    assert!(comment.contains("'Test Jazz'"), "comment: {comment}");
+    assert!(comment.contains("Top genres: Jazz"), "comment: {comment}");
+    assert!(!playlist.public);
+}
+
+#[test]
+fn config_sets_comment_template_and_visibility() {
+    let server = MockServer::start(sample_library(300));
+    let config = PlaylistConfig {
+        public: Some(true),
+        comment: Some("{songs} songs for {name}".to_string()),
+        ..jazz_config()
+    };
+    let dir = work_dir_with("visibility", vec![config]);
+
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    let playlist = &state.playlists[0];
+    assert_eq!(
+        playlist.comment.as_deref().map(ownership::strip_marker),
+        Some("8 songs for Test Jazz\n")
+    );
+    assert!(playlist.public);
+}
+
+#[test]
+fn hand_made_playlist_with_similar_name_is_left_alone() {
+    let server = MockServer::start(sample_library(300));
+    let personal = server
+        .state()
+        .add_playlist("Test Jazz Favourites", &["s1", "s2"]);
+    let dir = work_dir("collision");
+
+    let output = run(&server, &dir, &[]);
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 2);
+    let kept = state.playlists.iter().find(|p| p.id == personal).unwrap();
+    assert_eq!(kept.name, "Test Jazz Favourites");
+    assert_eq!(kept.song_ids, vec!["s1", "s2"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("'Test Jazz Favourites'"));
+}
+
+#[test]
+fn run_survives_a_failed_library_fetch() {
+    let server = MockServer::start(sample_library(300));
+    server
+        .state()
+        .fail_next
+        .insert("getRandomSongs".to_string(), 2);
+    let dir = work_dir("retry-fetch");
+
+    let output = run(&server, &dir, &[]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("getRandomSongs failed"), "{stdout}");
+    assert_eq!(server.state().requests_to("getRandomSongs").len(), 3);
+    assert_eq!(server.state().playlists.len(), 1);
+}
+
+#[test]
+fn undo_restores_songs_from_previous_run() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("undo");
+
+    run(&server, &dir, &[]);
+    let first_songs = server.state().playlists[0].song_ids.clone();
+    run(&server, &dir, &[]);
+    assert!(dir.join("data/run-history.json").exists());
+
+    run(&server, &dir, &["undo"]);
+
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].song_ids, first_songs);
+}
+
+#[test]
+fn undo_without_history_fails() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("undo-empty");
+
+    let output = command(&server, &dir).arg("undo").output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(server.state().requests_to("updatePlaylist").is_empty());
+}
+
+#[test]
+fn report_records_changes_against_previous_generation() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("report");
+
+    run(&server, &dir, &["--report", "first.json"]);
+    let output = run(&server, &dir, &["--report", "second.json"]);
+
+    let read = |file: &str| -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(dir.join(file)).unwrap()).unwrap()
+    };
+    let first = &read("first.json")["playlists"][0];
+    assert_eq!(first["status"], "published");
+    assert!(first["diff"].is_null());
+
+    let second = &read("second.json")["playlists"][0];
+    let state = server.state();
+    assert_eq!(second["config_name"], "Test Jazz");
+    assert_eq!(second["playlist_id"], state.playlists[0].id.as_str());
+    let diff = &second["diff"];
+    let count = |key: &str| diff[key].as_array().unwrap().len();
+    assert_eq!(count("kept") + count("added"), 8);
+    assert_eq!(count("kept") + count("removed"), 8);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Changes: "));
+}
+
+#[test]
+fn debug_run_shows_changes_without_writing() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("debug-diff");
+    run(&server, &dir, &[]);
+    let published = server.state().playlists[0].song_ids.clone();
+
+    let output = run(&server, &dir, &["--debug"]);
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Changes vs. current playlist: "));
+    assert_eq!(server.state().playlists[0].song_ids, published);
+}
+
+#[test]
+fn refresh_replaces_only_part_of_the_playlist() {
+    let server = MockServer::start(sample_library(300));
+    let config = PlaylistConfig {
+        refresh: Some(RefreshSettings {
+            replace_fraction: 0.25,
+        }),
+        ..jazz_config()
+    };
+    let dir = work_dir_with("refresh", vec![config]);
+
+    run(&server, &dir, &[]);
+    let first: Vec<String> = server.state().playlists[0].song_ids.clone();
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    let second = &state.playlists[0].song_ids;
+    assert_eq!(second.len(), 8);
+    let kept = second.iter().filter(|id| first.contains(id)).count();
+    assert_eq!(kept, 6);
+}
+
+#[test]
+fn exclusive_run_never_shares_songs_between_playlists() {
+    let server = MockServer::start(sample_library(300));
+    let named = |name: &str| PlaylistConfig {
+        name: name.to_string(),
+        ..jazz_config()
+    };
+    let configs = vec![
+        named("Jazz One"),
+        PlaylistConfig {
+            priority: 1,
+            ..named("Jazz Two")
+        },
+        PlaylistConfig {
+            allow_shared_songs: true,
+            ..named("Jazz Shared")
+        },
+    ];
+    let dir = work_dir_with("exclusive", configs);
+
+    let output = run(&server, &dir, &["--exclusive", "--report", "report.json"]);
+
+    let state = server.state();
+    let songs_of = |prefix: &str| -> Vec<String> {
+        let playlist = state
+            .playlists
+            .iter()
+            .find(|p| p.name.to_lowercase().starts_with(prefix))
+            .unwrap();
+        playlist.song_ids.clone()
+    };
+    let one = songs_of("jazz one");
+    let two = songs_of("jazz two");
+    assert_eq!(one.len(), 8);
+    assert_eq!(two.len(), 8);
+    assert!(one.iter().all(|id| !two.contains(id)));
+
+    // Jazz Two has the higher priority, so only Jazz One loses songs to it
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    let taken = |index: usize| report["playlists"][index]["songs_taken_by_earlier"].clone();
+    assert_eq!(taken(0), 8);
+    assert_eq!(taken(1), 0);
+    assert!(taken(2).is_null());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already used by earlier playlists"));
+}
+
+#[test]
+fn recently_generated_songs_are_not_repeated() {
+    let server = MockServer::start(sample_library(300));
+    let config = PlaylistConfig {
+        min_days_since_last_generated: Some(3),
+        ..jazz_config()
+    };
+    let dir = work_dir_with("recently-generated", vec![config]);
+
+    run(&server, &dir, &[]);
+    let first: Vec<String> = server.state().playlists[0].song_ids.clone();
+    assert!(dir.join("data").join("placements.json").exists());
+    run(&server, &dir, &[]);
+
+    let state = server.state();
+    let second = &state.playlists[0].song_ids;
+    assert_eq!(second.len(), 8);
+    assert!(second.iter().all(|id| !first.contains(id)));
+}
+
+#[test]
+fn rotation_surfaces_new_songs_and_reports_coverage() {
+    let server = MockServer::start(sample_library(300));
+    let config = PlaylistConfig {
+        rotation: Some(RotationSettings {
+            weight: 10_000.0,
+            period_days: 28,
+        }),
+        ..jazz_config()
+    };
+    let dir = work_dir_with("rotation", vec![config]);
+    let coverage = |dir: &Path| -> serde_json::Value {
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap())
+                .unwrap();
+        report["playlists"][0]["coverage"].clone()
+    };
+
+    run(&server, &dir, &["--report", "report.json"]);
+    let first: Vec<String> = server.state().playlists[0].song_ids.clone();
+    let eligible = coverage(&dir)["eligible"].as_u64().unwrap();
+    assert!(eligible > 16);
+    assert_eq!(coverage(&dir)["surfaced"], 8);
+
+    let output = run(&server, &dir, &["--report", "report.json"]);
+    let state = server.state();
+    let second = &state.playlists[0].song_ids;
+    // Without rotation the second run would publish the same songs again
+    let repeated = second.iter().filter(|id| first.contains(id)).count();
+    assert!(repeated < 8);
+    assert_eq!(coverage(&dir)["surfaced"], 16 - repeated);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("eligible songs surfaced"));
+}
+
+#[test]
+fn coverage_ignores_playlists_that_were_not_published() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("coverage-debug");
+
+    run(&server, &dir, &["--debug", "--report", "report.json"]);
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["debug"], true);
+    assert_eq!(report["playlists"][0]["coverage"]["surfaced"], 0);
+    assert!(server.state().playlists.is_empty());
+}
+
+#[test]
+fn same_seed_reproduces_the_run() {
+    let server = MockServer::start(sample_library(300));
+    let config = PlaylistConfig {
+        preference_weights: PreferenceWeights {
+            randomness_factor: 5.0,
+            ..PlaylistConfig::default().preference_weights
+        },
+        ..jazz_config()
+    };
+    let dir = work_dir_with("seed", vec![config]);
+    let generated = |seed: &str| {
+        let output = run(
+            &server,
+            &dir,
+            &["--debug", "--seed", seed, "--report", "report.json"],
+        );
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap())
+                .unwrap();
+        assert_eq!(report["seed"].to_string(), seed);
+        // The debug listing ends each song with its ID
+        let songs: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.contains("| ID: "))
+            .map(str::to_string)
+            .collect();
+        (report["playlists"][0]["name"].clone(), songs)
+    };
+
+    let first = generated("42");
+    assert_eq!(first.1.len(), 8);
+    assert_eq!(generated("42"), first);
+    assert_ne!(generated("43"), first);
+}
+
+#[test]
+fn reference_time_reproduces_the_run_on_a_later_day() {
+    // Every ninth song was played the day before the reference time: too recent then,
+    // but long enough ago by the clock for the rule to let it in
+    let mut library = sample_library(300);
+    for song in library.iter_mut().step_by(9) {
+        song.played = Some("2024-03-04T12:00:00Z".to_string());
+    }
+    let server = MockServer::start(library);
+    let config = PlaylistConfig {
+        min_days_since_last_play: Some(7),
+        preference_weights: PreferenceWeights {
+            randomness_factor: 5.0,
+            ..PlaylistConfig::default().preference_weights
+        },
+        ..jazz_config()
+    };
+    let dir = work_dir_with("reference-time", vec![config]);
+    let generated = || {
+        let output = command(&server, &dir)
+            .env("TZ", "UTC")
+            .args(["--debug", "--seed", "42", "--report", "report.json"])
+            .args(["--reference-time", "2024-03-05T12:00:00Z"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let report: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap())
+                .unwrap();
+        assert_eq!(report["reference_time"], "2024-03-05T12:00:00+00:00");
+        let lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.contains("| ID: ") || line.contains("Comment: "))
+            .map(str::to_string)
+            .collect();
+        (report["playlists"][0]["name"].clone(), lines)
+    };
+
+    let first = generated();
+    assert_eq!(generated(), first);
+
+    let (name, lines) = &first;
+    assert!(name.as_str().unwrap().contains("tuesday"));
+    assert!(lines[0].contains("on 2024-03-05."));
+    let ids: Vec<usize> = lines
+        .iter()
+        .filter_map(|line| line.split("| ID: s").nth(1))
+        .map(|id| id.trim().parse().unwrap())
+        .collect();
+    assert_eq!(ids.len(), 8);
+    assert!(ids.iter().all(|id| id % 9 != 0));
+}
+
+#[test]
+fn saved_library_snapshot_reproduces_the_run_offline() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("snapshot");
+    let song_lines = |output: &Output| -> Vec<String> {
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.contains("| ID: "))
+            .map(str::to_string)
+            .collect()
+    };
+
+    let online = run(
+        &server,
+        &dir,
+        &["--debug", "--seed", "7", "--save-library", "library.json"],
+    );
+    assert!(dir.join("library.json").exists());
+    let requests_before = server.state().requests.len();
+
+    // Without any server settings, so the run cannot reach the network
+    let offline = command(&server, &dir)
+        .env_remove("BASE_URL")
+        .env_remove("USERNAME")
+        .env_remove("PASSWORD")
+        .args(["--debug", "--seed", "7", "--library", "library.json"])
+        .output()
+        .unwrap();
+    assert!(
+        offline.status.success(),
+        "{}",
+        String::from_utf8_lossy(&offline.stderr)
+    );
+
+    assert_eq!(song_lines(&online).len(), 8);
+    assert_eq!(song_lines(&offline), song_lines(&online));
+    assert_eq!(server.state().requests.len(), requests_before);
+}
+
+#[test]
+fn offline_run_generates_as_of_the_snapshot_time() {
+    // Every ninth song was played the day before the snapshot was saved
+    let mut library = sample_library(300);
+    for song in library.iter_mut().step_by(9) {
+        song.played = Some("2024-03-04T12:00:00Z".to_string());
+    }
+    let server = MockServer::start(Vec::new());
+    let config = PlaylistConfig {
+        min_days_since_last_play: Some(7),
+        ..jazz_config()
+    };
+    let dir = work_dir_with("snapshot-time", vec![config]);
+    let saved_at = "2024-03-05T12:00:00Z".parse().unwrap();
+    LibrarySnapshot::new(library, saved_at)
+        .save(&dir.join("library.json"))
+        .unwrap();
+
+    let output = command(&server, &dir)
+        .env_remove("BASE_URL")
+        .env_remove("USERNAME")
+        .env_remove("PASSWORD")
+        .env("TZ", "UTC")
+        .args(["--debug", "--library", "library.json"])
+        .args(["--report", "report.json"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+    assert_eq!(report["reference_time"], "2024-03-05T12:00:00+00:00");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("on 2024-03-05."), "{stdout}");
+    let ids: Vec<usize> = stdout
+        .lines()
+        .filter_map(|line| line.split("| ID: s").nth(1))
+        .map(|id| id.trim().parse().unwrap())
+        .collect();
+    assert_eq!(ids.len(), 8);
+    assert!(ids.iter().all(|id| id % 9 != 0));
+}
+
+#[test]
+fn debug_run_does_not_write_to_server() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("debug");
+
+    run(&server, &dir, &["--debug"]);
+
+    let state = server.state();
+    assert!(state.playlists.is_empty());
+    assert!(state.requests.iter().all(|r| !matches!(
+        r.endpoint.as_str(),
+        "createPlaylist" | "updatePlaylist" | "deletePlaylist"
+    )));
+}
+
+#[test]
+fn logs_never_contain_credentials() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("redaction");
+
+    let output = command(&server, &dir)
+        .env("REQUEST_LOG", "verbose")
+        .output()
+        .unwrap();
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("/rest/createPlaylist"));
+    assert!(stdout.contains("u=***&t=***&s=***"));
+    assert!(!stdout.contains(USERNAME));
+    assert!(!stdout.contains(PASSWORD));
+}
+
+#[test]
+fn use_cache_refreshes_only_changed_albums_and_works_offline() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("use-cache");
+
+    let first = run(&server, &dir, &["--use-cache", "--debug"]);
+    let stdout = String::from_utf8_lossy(&first.stdout);
+    assert!(
+        stdout.contains("0 albums unchanged, 10 refreshed, 0 removed"),
+        "{stdout}"
+    );
+    assert!(dir.join("data/library-cache.json").exists());
+
+    server.state().songs[0].starred = Some("2024-05-01T10:00:00Z".to_string());
+    let second = run(&server, &dir, &["--use-cache", "--debug"]);
+    let stdout = String::from_utf8_lossy(&second.stdout);
+    assert!(
+        stdout.contains("9 albums unchanged, 1 refreshed, 0 removed"),
+        "{stdout}"
+    );
+    assert_eq!(server.state().requests_to("getAlbum").len(), 11);
+
+    // Nothing listens on port 1, so the run has to use the cached songs
+    let offline = command(&server, &dir)
+        .env("BASE_URL", "http://127.0.0.1:1")
+        .env("HTTP_MAX_RETRIES", "0")
+        .args(["--use-cache", "--debug"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&offline.stdout);
+    assert!(offline.status.success(), "{stdout}");
+    assert!(
+        stdout.contains("Server unreachable - using library cache"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Generated 8 songs"), "{stdout}");
+}
+
+#[test]
+fn corrupt_cache_is_rebuilt_instead_of_failing_the_run() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("corrupt-cache");
+    std::fs::create_dir_all(dir.join("data")).unwrap();
+    std::fs::write(dir.join("data/library-cache.json"), "{\"version\":1,\"albu").unwrap();
+
+    let output = run(&server, &dir, &["--use-cache", "--debug"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("is corrupt"), "{stdout}");
+    assert!(
+        stdout.contains("0 albums unchanged, 10 refreshed, 0 removed"),
+        "{stdout}"
+    );
+    assert!(dir.join("data/library-cache.json").exists());
+}
+
+#[test]
+fn rebuild_cache_refetches_every_album() {
+    let server = MockServer::start(sample_library(300));
+    let dir = work_dir("rebuild-cache");
+
+    run(&server, &dir, &["--use-cache", "--debug"]);
+    let output = run(&server, &dir, &["rebuild-cache"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Library cache rebuilt: 10 albums, 300 songs"),
+        "{stdout}"
+    );
+    assert_eq!(server.state().requests_to("getAlbum").len(), 20);
+}
+
+#[test]
+fn server_info_prints_capabilities() {
+    let server = MockServer::start(sample_library(30));
+    let dir = work_dir("server-info");
+
+    let output = run(&server, &dir, &["server-info"]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Server:        mock 0.1"), "{stdout}");
+    assert!(stdout.contains("API version:   1.16.1"), "{stdout}");
+    assert!(stdout.contains("Multiple genres:   yes"), "{stdout}");
+    assert!(stdout.contains("Form POST:         yes"), "{stdout}");
+    assert!(stdout.contains("API key auth:      no"), "{stdout}");
+    assert!(stdout.contains("Lyrics:            no"), "{stdout}");
+    assert!(stdout.contains("formPost (v1)"), "{stdout}");
+    assert!(server.state().playlists.is_empty());
+}
+
+#[test]
+fn server_info_fails_early_when_api_key_is_unsupported() {
+    let server = MockServer::start(sample_library(30));
+    let dir = work_dir("server-info-api-key");
+
+    let output = command(&server, &dir)
+        .env_remove("USERNAME")
+        .env_remove("PASSWORD")
+        .env("API_KEY", API_KEY)
+        .arg("server-info")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("apiKeyAuthentication"), "{stderr}");
+}
@@ -0,0 +1,823 @@
+//! Client tests against the in-process mock server
+#![cfg(feature = "subsonic")]
+
+mod mock_server;
+
+use mock_server::{API_KEY, MockServer, PASSWORD, USERNAME, sample_library};
+use playlist_generator::client::SubsonicClient;
+use playlist_generator::config::{Auth, Config, HttpPolicy, RequestLog};
+use playlist_generator::error::SubsonicError;
+use playlist_generator::library_cache::LibraryCache;
+use playlist_generator::models::Genre;
+use playlist_generator::ownership;
+use playlist_generator::playlist::DuplicatePolicy;
+use playlist_generator::source::{DuplicateAction, PlaylistSink, PlaylistUpload};
+use std::time::Duration;
+
+fn client_with_auth(server: &MockServer, auth: Auth) -> SubsonicClient {
+    SubsonicClient::new(Config {
+        base_url: server.base_url.clone(),
+        auth,
+        http: HttpPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..HttpPolicy::default()
+        },
+        request_log: RequestLog::Off,
+    })
+}
+
+fn client(server: &MockServer) -> SubsonicClient {
+    client_with_auth(
+        server,
+        Auth::Token {
+            username: USERNAME.to_string(),
+            password: PASSWORD.to_string(),
+        },
+    )
+}
+
+fn ids(range: std::ops::Range<usize>) -> Vec<String> {
+    range.map(|i| format!("s{i}")).collect()
+}
+
+fn upload<'a>(name: &'a str, song_ids: &'a [String]) -> PlaylistUpload<'a> {
+    PlaylistUpload {
+        name,
+        config_name: "Morning Chill",
+        song_ids,
+        comment: "Generated for tests",
+        public: None,
+        duplicates: DuplicatePolicy::Delete,
+        keep_history: None,
+    }
+}
+
+/// Add a playlist that looks like an earlier run of the "Morning Chill" config
+fn add_generated(server: &MockServer, name: &str, song_ids: &[&str]) -> String {
+    let mut state = server.state();
+    let id = state.add_playlist(name, song_ids);
+    state.playlists.last_mut().unwrap().comment = Some(ownership::marker("Morning Chill"));
+    id
+}
+
+#[test]
+fn pattern_cleanup_creates_playlist_when_nothing_is_owned() {
+    let server = MockServer::start(sample_library(30));
+    server.state().add_playlist("Workout", &["s1"]);
+    let client = client(&server);
+    let song_ids = ids(0..5);
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap();
+
+    let state = server.state();
+    let playlist = state.playlist_named("Morning Chill friday").unwrap();
+    assert_eq!(playlist.id, outcome.playlist_id);
+    assert!(!outcome.updated_existing);
+    assert_eq!(playlist.song_ids, song_ids);
+    assert_eq!(
+        ownership::owner_config(playlist.comment.as_deref().unwrap()),
+        Some("Morning Chill")
+    );
+    assert_eq!(state.playlists.len(), 2);
+    assert_eq!(
+        state.requests_to("createPlaylist")[0].params_named("songId"),
+        vec!["s0", "s1", "s2", "s3", "s4"]
+    );
+}
+
+#[test]
+fn pattern_cleanup_updates_owned_playlist_in_place() {
+    let server = MockServer::start(sample_library(30));
+    let existing = server
+        .state()
+        .add_playlist("morning chill monday", &["s20", "s21", "s22"]);
+    server.state().playlists[0].comment = Some(ownership::marker("Morning Chill"));
+    let client = client(&server);
+    let song_ids = ids(0..4);
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap();
+
+    let state = server.state();
+    assert_eq!(outcome.playlist_id, existing);
+    assert!(outcome.updated_existing);
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].name, "Morning Chill friday");
+    assert_eq!(state.playlists[0].song_ids, song_ids);
+    assert!(state.requests_to("createPlaylist").is_empty());
+}
+
+#[test]
+fn pattern_cleanup_never_touches_hand_made_playlists() {
+    let server = MockServer::start(sample_library(30));
+    let mut state = server.state();
+    state.add_playlist("Morning Chill Classics", &["s20", "s21"]);
+    state.add_playlist("Morning Chill friday", &["s22"]);
+    state.add_playlist("Party Mix", &["s23"]);
+    state.playlists[2].comment = Some(ownership::marker("Party Mix"));
+    drop(state);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap();
+
+    let state = server.state();
+    assert_eq!(
+        outcome.collisions,
+        vec!["Morning Chill Classics", "Morning Chill friday"]
+    );
+    assert_eq!(state.playlists.len(), 4);
+    assert_eq!(state.playlists[0].song_ids, vec!["s20", "s21"]);
+    assert_eq!(state.playlists[1].song_ids, vec!["s22"]);
+    assert_eq!(state.playlists[2].song_ids, vec!["s23"]);
+    assert!(state.requests_to("deletePlaylist").is_empty());
+    let playlist_ids: Vec<&str> = state
+        .requests_to("updatePlaylist")
+        .iter()
+        .filter_map(|r| r.param("playlistId"))
+        .collect();
+    assert!(playlist_ids.iter().all(|id| *id == outcome.playlist_id));
+}
+
+#[test]
+fn playlists_of_a_config_with_a_longer_name_are_not_collisions() {
+    let server = MockServer::start(sample_library(30));
+    let client = client(&server);
+    let song_ids = ids(0..3);
+    let party_mix = PlaylistUpload {
+        config_name: "Party Mix",
+        ..upload("Party Mix", &song_ids)
+    };
+    let party_mix_classics = PlaylistUpload {
+        config_name: "Party Mix Classics",
+        ..upload("Party Mix Classics", &song_ids)
+    };
+
+    client
+        .create_playlist_with_pattern_cleanup(&party_mix)
+        .unwrap();
+    client
+        .create_playlist_with_pattern_cleanup(&party_mix_classics)
+        .unwrap();
+    let rerun = client
+        .create_playlist_with_pattern_cleanup(&party_mix)
+        .unwrap();
+
+    assert!(rerun.collisions.is_empty());
+    assert!(rerun.updated_existing);
+    assert_eq!(server.state().playlists.len(), 2);
+}
+
+#[test]
+fn config_name_with_brackets_recognises_its_own_playlist() {
+    let server = MockServer::start(sample_library(30));
+    let client = client(&server);
+    let song_ids = ids(0..3);
+    let live = PlaylistUpload {
+        config_name: "Mix [Live]",
+        ..upload("Mix [Live] friday", &song_ids)
+    };
+
+    let first = client.create_playlist_with_pattern_cleanup(&live).unwrap();
+    let rerun = client.create_playlist_with_pattern_cleanup(&live).unwrap();
+
+    assert_eq!(rerun.playlist_id, first.playlist_id);
+    assert!(rerun.updated_existing);
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(
+        ownership::owner_config(state.playlists[0].comment.as_deref().unwrap()),
+        Some("Mix [Live]")
+    );
+}
+
+#[test]
+fn duplicate_generated_playlists_are_deleted_by_default() {
+    let server = MockServer::start(sample_library(30));
+    add_generated(&server, "Morning Chill monday", &["s20"]);
+    let current = add_generated(&server, "Morning Chill friday", &["s21"]);
+    add_generated(&server, "Morning Chill tuesday", &["s22"]);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap();
+
+    assert_eq!(outcome.playlist_id, current);
+    let reconciled: Vec<(&str, &DuplicateAction)> = outcome
+        .duplicates
+        .iter()
+        .map(|d| (d.name.as_str(), &d.action))
+        .collect();
+    assert_eq!(
+        reconciled,
+        vec![
+            ("Morning Chill monday", &DuplicateAction::Deleted),
+            ("Morning Chill tuesday", &DuplicateAction::Deleted),
+        ]
+    );
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].id, current);
+    assert_eq!(state.playlists[0].song_ids, song_ids);
+}
+
+#[test]
+fn archived_duplicates_are_renamed_and_no_longer_managed() {
+    let server = MockServer::start(sample_library(30));
+    let current = add_generated(&server, "Morning Chill friday", &["s20"]);
+    let old = add_generated(&server, "Morning Chill monday", &["s21"]);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+    let archiving = PlaylistUpload {
+        duplicates: DuplicatePolicy::Archive,
+        ..upload("Morning Chill friday", &song_ids)
+    };
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&archiving)
+        .unwrap();
+    let rerun = client
+        .create_playlist_with_pattern_cleanup(&archiving)
+        .unwrap();
+
+    assert!(matches!(
+        &outcome.duplicates[0].action,
+        DuplicateAction::Archived(name) if name.starts_with("Morning Chill monday (archived ")
+    ));
+    assert_eq!(rerun.playlist_id, current);
+    assert!(rerun.duplicates.is_empty());
+    assert!(rerun.collisions.is_empty());
+    let state = server.state();
+    let archived = state.playlists.iter().find(|p| p.id == old).unwrap();
+    assert_eq!(archived.song_ids, vec!["s21"]);
+    assert_eq!(
+        ownership::archived_config(archived.comment.as_deref().unwrap()),
+        Some("Morning Chill")
+    );
+}
+
+#[test]
+fn kept_duplicates_are_only_reported() {
+    let server = MockServer::start(sample_library(30));
+    add_generated(&server, "Morning Chill friday", &["s20"]);
+    add_generated(&server, "Morning Chill monday", &["s21"]);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&PlaylistUpload {
+            duplicates: DuplicatePolicy::Keep,
+            ..upload("Morning Chill friday", &song_ids)
+        })
+        .unwrap();
+
+    assert_eq!(outcome.duplicates[0].action, DuplicateAction::Kept);
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 2);
+    assert_eq!(state.playlists[1].name, "Morning Chill monday");
+    assert_eq!(state.playlists[1].song_ids, vec!["s21"]);
+}
+
+#[test]
+fn keep_history_archives_previous_generation_and_prunes_old_archives() {
+    let server = MockServer::start(sample_library(30));
+    let previous = add_generated(&server, "Morning Chill monday", &["s20", "s21"]);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+    let with_history = |name| PlaylistUpload {
+        keep_history: Some(2),
+        ..upload(name, &song_ids)
+    };
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&with_history("Morning Chill tuesday"))
+        .unwrap();
+
+    assert!(!outcome.updated_existing);
+    assert_ne!(outcome.playlist_id, previous);
+    let archived_name = outcome.archived.unwrap();
+    assert!(archived_name.starts_with("Morning Chill monday (archived "));
+    {
+        let state = server.state();
+        let archived = state.playlists.iter().find(|p| p.id == previous).unwrap();
+        assert_eq!(archived.name, archived_name);
+        assert_eq!(archived.song_ids, vec!["s20", "s21"]);
+        assert_eq!(
+            state
+                .playlist_named("Morning Chill tuesday")
+                .unwrap()
+                .song_ids,
+            song_ids
+        );
+    }
+
+    client
+        .create_playlist_with_pattern_cleanup(&with_history("Morning Chill wednesday"))
+        .unwrap();
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&with_history("Morning Chill thursday"))
+        .unwrap();
+
+    // The oldest archive goes once there are more than two
+    assert_eq!(outcome.pruned_archives, vec![archived_name]);
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 3);
+    assert!(state.playlists.iter().all(|p| p.id != previous));
+    assert!(outcome.collisions.is_empty());
+    assert!(outcome.duplicates.is_empty());
+}
+
+#[test]
+fn keep_history_zero_updates_in_place_and_prunes_every_archive() {
+    let server = MockServer::start(sample_library(30));
+    let previous = add_generated(&server, "Morning Chill monday", &["s20", "s21"]);
+    let mut state = server.state();
+    state.add_playlist("Morning Chill sunday (archived 2024-01-01)", &["s22"]);
+    state.playlists.last_mut().unwrap().comment = Some(ownership::archive_marker("Morning Chill"));
+    drop(state);
+    let client = client(&server);
+    let song_ids = ids(0..3);
+    let without_history = PlaylistUpload {
+        keep_history: Some(0),
+        ..upload("Morning Chill tuesday", &song_ids)
+    };
+
+    let outcome = client
+        .create_playlist_with_pattern_cleanup(&without_history)
+        .unwrap();
+
+    assert!(outcome.updated_existing);
+    assert_eq!(outcome.playlist_id, previous);
+    assert_eq!(outcome.archived, None);
+    assert_eq!(
+        outcome.pruned_archives,
+        vec!["Morning Chill sunday (archived 2024-01-01)"]
+    );
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].name, "Morning Chill tuesday");
+    assert_eq!(state.playlists[0].song_ids, song_ids);
+}
+
+#[test]
+fn update_playlist_sends_one_form_post_when_supported() {
+    let server = MockServer::start(sample_library(30));
+    let existing = server.state().add_playlist("Mix", &["s20", "s21"]);
+    let client = client(&server);
+
+    client
+        .update_playlist(&existing, "Mix", &ids(0..120))
+        .unwrap();
+
+    let state = server.state();
+    assert_eq!(state.playlists[0].song_ids, ids(0..120));
+    let updates = state.requests_to("updatePlaylist");
+    assert_eq!(updates.len(), 1);
+    assert_eq!(updates[0].method, "POST");
+    assert_eq!(updates[0].params_named("songIndexToRemove"), vec!["0", "1"]);
+}
+
+#[test]
+fn update_playlist_falls_back_to_chunked_gets() {
+    let server = MockServer::start(sample_library(30));
+    server.state().extensions.clear();
+    let old_ids = ids(200..320);
+    let old_refs: Vec<&str> = old_ids.iter().map(String::as_str).collect();
+    let existing = server.state().add_playlist("Mix", &old_refs);
+    let client = client(&server);
+
+    client
+        .update_playlist(&existing, "Mix", &ids(0..130))
+        .unwrap();
+
+    let state = server.state();
+    assert_eq!(state.playlists[0].song_ids, ids(0..130));
+    let updates = state.requests_to("updatePlaylist");
+    assert!(updates.len() > 2);
+    for update in updates {
+        assert_eq!(update.method, "GET");
+        let changes = update.params_named("songIndexToRemove").len()
+            + update.params_named("songIdToAdd").len();
+        assert!(changes <= 50, "request carried {changes} changes");
+    }
+}
+
+#[test]
+fn chunked_update_adds_new_songs_before_removing_old_ones() {
+    let server = MockServer::start(sample_library(30));
+    server.state().extensions.clear();
+    let existing = server.state().add_playlist("Mix", &["s20", "s21", "s22"]);
+    let client = client(&server);
+
+    client
+        .update_playlist(&existing, "Mix", &ids(0..60))
+        .unwrap();
+
+    let state = server.state();
+    let updates = state.requests_to("updatePlaylist");
+    assert_eq!(updates.len(), 3);
+    assert!(updates[0].params_named("songIndexToRemove").is_empty());
+    assert!(updates[1].params_named("songIndexToRemove").is_empty());
+    assert_eq!(
+        updates[2].params_named("songIndexToRemove"),
+        vec!["2", "1", "0"]
+    );
+    assert_eq!(state.playlists[0].song_ids, ids(0..60));
+}
+
+#[test]
+fn failed_replacement_restores_previous_tracks() {
+    let server = MockServer::start(sample_library(30));
+    let existing = server
+        .state()
+        .add_playlist("Morning Chill monday", &["s20", "s21"]);
+    server.state().playlists[0].comment = Some(ownership::marker("Morning Chill"));
+    server.state().max_playlist_len = Some(10);
+    let client = client(&server);
+    let song_ids = ids(0..12);
+
+    let error = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap_err();
+
+    assert!(format!("{error:#}").contains("restored to its previous tracks"));
+    let state = server.state();
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].id, existing);
+    assert_eq!(state.playlists[0].name, "Morning Chill monday");
+    assert_eq!(state.playlists[0].song_ids, vec!["s20", "s21"]);
+}
+
+#[test]
+fn incomplete_new_playlist_is_removed() {
+    let server = MockServer::start(sample_library(30));
+    server.state().max_playlist_len = Some(10);
+    let client = client(&server);
+    let song_ids = ids(0..12);
+
+    let error = client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &song_ids))
+        .unwrap_err();
+
+    assert!(format!("{error:#}").contains("Removed incomplete playlist"));
+    assert!(server.state().playlists.is_empty());
+}
+
+#[test]
+fn empty_song_list_never_replaces_a_playlist() {
+    let server = MockServer::start(sample_library(30));
+    server
+        .state()
+        .add_playlist("Morning Chill monday", &["s20", "s21"]);
+    server.state().playlists[0].comment = Some(ownership::marker("Morning Chill"));
+    let client = client(&server);
+
+    client
+        .create_playlist_with_pattern_cleanup(&upload("Morning Chill friday", &[]))
+        .unwrap_err();
+
+    let state = server.state();
+    assert_eq!(state.playlists[0].song_ids, vec!["s20", "s21"]);
+    assert!(state.requests_to("updatePlaylist").is_empty());
+}
+
+#[test]
+fn create_playlist_is_retried_when_nothing_was_created() {
+    let server = MockServer::start(sample_library(30));
+    server
+        .state()
+        .fail_next
+        .insert("createPlaylist".to_string(), 1);
+    let client = client(&server);
+
+    client.create_playlist("Retry Me", &ids(0..3)).unwrap();
+
+    let state = server.state();
+    assert_eq!(state.requests_to("createPlaylist").len(), 2);
+    assert_eq!(state.playlists.len(), 1);
+    assert_eq!(state.playlists[0].song_ids, ids(0..3));
+}
+
+#[test]
+fn reads_are_retried_with_backoff_after_server_errors() {
+    let server = MockServer::start(sample_library(30));
+    let mut state = server.state();
+    let playlist = state.add_playlist("Mix", &["s1", "s2"]);
+    state.fail_next.insert("getRandomSongs".to_string(), 2);
+    state.fail_next.insert("getPlaylist".to_string(), 1);
+    drop(state);
+    let client = SubsonicClient::new(Config {
+        base_url: server.base_url.clone(),
+        auth: Auth::Token {
+            username: USERNAME.to_string(),
+            password: PASSWORD.to_string(),
+        },
+        http: HttpPolicy {
+            initial_backoff: Duration::from_millis(20),
+            ..HttpPolicy::default()
+        },
+        request_log: RequestLog::Off,
+    });
+
+    let started = std::time::Instant::now();
+    let songs = client.fetch_songs(Some(10)).unwrap();
+    // Two retries wait 20ms and then 40ms
+    assert!(started.elapsed() >= Duration::from_millis(60));
+    let entries = client.get_playlist_entries(&playlist).unwrap();
+
+    assert_eq!(songs.len(), 10);
+    assert_eq!(entries.len(), 2);
+    let state = server.state();
+    assert_eq!(state.requests_to("getRandomSongs").len(), 3);
+    assert_eq!(state.requests_to("getPlaylist").len(), 2);
+}
+
+#[test]
+fn partially_applied_update_is_reported_instead_of_resent() {
+    let server = MockServer::start(sample_library(30));
+    let mut state = server.state();
+    let existing = state.add_playlist("Mix", &["s20", "s21"]);
+    // The old tracks are removed, then the server fails before adding the new ones
+    state.partial_update_next = 1;
+    drop(state);
+    let client = client(&server);
+
+    let error = client
+        .update_playlist(&existing, "Mix", &ids(0..3))
+        .unwrap_err();
+
+    assert!(
+        format!("{error:#}").contains("partially updated"),
+        "{error:#}"
+    );
+    let state = server.state();
+    assert_eq!(state.requests_to("updatePlaylist").len(), 1);
+    assert!(state.playlists[0].song_ids.is_empty());
+}
+
+#[test]
+fn delete_playlist_removes_it_and_reports_missing_ones() {
+    let server = MockServer::start(sample_library(30));
+    let id = server.state().add_playlist("Old", &["s1"]);
+    let client = client(&server);
+
+    client.delete_playlist(&id).unwrap();
+    assert!(server.state().playlists.is_empty());
+
+    let error = client.delete_playlist(&id).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<SubsonicError>(),
+        Some(SubsonicError::NotFound(_))
+    ));
+}
+
+#[test]
+fn api_key_is_sent_instead_of_username_on_every_request() {
+    let server = MockServer::start(sample_library(30));
+    server
+        .state()
+        .extensions
+        .push("apiKeyAuthentication".to_string());
+    let client = client_with_auth(&server, Auth::ApiKey(API_KEY.to_string()));
+
+    client.ping().unwrap();
+    client.create_playlist("Keyed", &ids(0..2)).unwrap();
+
+    let state = server.state();
+    assert!(state.requests.len() > 2);
+    for request in &state.requests {
+        assert_eq!(request.param("u"), None);
+        assert_eq!(request.param("apiKey"), Some(API_KEY));
+    }
+}
+
+#[test]
+fn genres_array_is_only_used_when_the_server_supports_it() {
+    let mut songs = sample_library(30);
+    for song in &mut songs {
+        song.genres = Some(vec![
+            Genre {
+                name: song.genre.clone().unwrap(),
+            },
+            Genre {
+                name: "Soundtrack".to_string(),
+            },
+        ]);
+    }
+    let open_subsonic = MockServer::start(songs.clone());
+    let plain = MockServer::start(songs);
+    plain.state().plain_subsonic = true;
+
+    let multi = client(&open_subsonic).fetch_songs(Some(30)).unwrap();
+    let single = client(&plain).fetch_songs(Some(30)).unwrap();
+
+    assert!(
+        client(&open_subsonic)
+            .capabilities()
+            .supports_multiple_genres()
+    );
+    assert!(multi.iter().all(|song| song.get_all_genres().len() == 2));
+    assert!(!client(&plain).capabilities().supports_multiple_genres());
+    assert!(single.iter().all(|song| song.genres.is_none()));
+    assert!(single.iter().all(|song| song.get_all_genres().len() == 1));
+}
+
+#[test]
+fn api_key_without_server_extension_fails_on_ping() {
+    let server = MockServer::start(sample_library(30));
+    let client = client_with_auth(&server, Auth::ApiKey(API_KEY.to_string()));
+
+    let error = client.ping().unwrap_err().to_string();
+
+    assert!(error.contains("apiKeyAuthentication"), "{error}");
+    assert!(error.contains("USERNAME and PASSWORD"), "{error}");
+}
+
+#[test]
+fn rejected_token_auth_switches_to_encoded_password() {
+    let server = MockServer::start(sample_library(30));
+    server.state().reject_token_auth = true;
+    let client = client(&server);
+
+    client.ping().unwrap();
+    client.get_playlists().unwrap();
+
+    let state = server.state();
+    let playlists_request = &state.requests_to("getPlaylists")[0];
+    assert_eq!(playlists_request.param("t"), None);
+    assert!(playlists_request.param("p").unwrap().starts_with("enc:"));
+}
+
+#[test]
+fn wrong_password_is_reported_as_auth_failure() {
+    let server = MockServer::start(sample_library(30));
+    let client = client_with_auth(
+        &server,
+        Auth::Token {
+            username: USERNAME.to_string(),
+            password: "wrong".to_string(),
+        },
+    );
+
+    let error = client.ping().unwrap_err();
+    let error = error.downcast_ref::<SubsonicError>().unwrap();
+    assert!(error.is_auth_failure());
+    assert_eq!(error.hint(), Some("check USERNAME and PASSWORD in .env"));
+}
+
+#[test]
+fn invalid_api_key_hint_points_at_api_key() {
+    let server = MockServer::start(sample_library(30));
+    server
+        .state()
+        .extensions
+        .push("apiKeyAuthentication".to_string());
+    let client = client_with_auth(&server, Auth::ApiKey("not-the-key".to_string()));
+
+    let error = client.ping().unwrap_err();
+    let error = error.downcast_ref::<SubsonicError>().unwrap();
+    assert!(matches!(error, SubsonicError::InvalidApiKey(_)));
+    assert_eq!(error.hint(), Some("check API_KEY in .env"));
+}
+
+#[test]
+fn full_library_walk_pages_through_albums_and_skips_duplicate_songs() {
+    // One album per song, enough for three getAlbumList2 pages
+    let mut songs = sample_library(1001);
+    for (i, song) in songs.iter_mut().enumerate() {
+        song.album_id = Some(format!("al{i}"));
+    }
+    // A song listed on two albums, like a compilation track
+    let mut on_compilation = songs[0].clone();
+    on_compilation.album_id = Some("al1".to_string());
+    songs.push(on_compilation);
+    let server = MockServer::start(songs);
+    let client = client(&server);
+
+    let mut progress_calls = 0;
+    let songs = client
+        .fetch_all_songs(|progress| {
+            progress_calls += 1;
+            assert_eq!(progress.albums_total, 1001);
+        })
+        .unwrap();
+
+    assert_eq!(songs.len(), 1001);
+    let mut song_ids: Vec<&str> = songs.iter().map(|s| s.id.as_str()).collect();
+    song_ids.sort_unstable();
+    song_ids.dedup();
+    assert_eq!(song_ids.len(), 1001);
+    assert_eq!(progress_calls, 1001);
+
+    let state = server.state();
+    let pages = state.requests_to("getAlbumList2");
+    let offsets: Vec<&str> = pages.iter().map(|r| r.param("offset").unwrap()).collect();
+    assert_eq!(offsets, ["0", "500", "1000"]);
+    assert!(pages.iter().all(|r| r.param("size") == Some("500")));
+    assert_eq!(state.requests_to("getAlbum").len(), 1001);
+}
+
+#[test]
+fn library_cache_only_refetches_changed_albums() {
+    // 30 songs on 10 albums
+    let server = MockServer::start(sample_library(30));
+    let client = client(&server);
+    let mut cache = LibraryCache::new();
+
+    let stats = cache.refresh(&client, |_| {}).unwrap();
+    assert_eq!(
+        (stats.unchanged, stats.refreshed, stats.removed),
+        (0, 10, 0)
+    );
+    assert_eq!(cache.song_count(), 30);
+
+    let stats = cache.refresh(&client, |_| {}).unwrap();
+    assert_eq!(
+        (stats.unchanged, stats.refreshed, stats.removed),
+        (10, 0, 0)
+    );
+    assert_eq!(server.state().requests_to("getAlbum").len(), 10);
+
+    {
+        let mut state = server.state();
+        // A new song on al3, a play on al5 and al7 removed entirely
+        let mut added = state.songs[3].clone();
+        added.id = "s-new".to_string();
+        state.songs.push(added);
+        state.songs[5].play_count = Some(100);
+        state.songs.retain(|s| s.album_id.as_deref() != Some("al7"));
+    }
+    let stats = cache.refresh(&client, |_| {}).unwrap();
+    assert_eq!((stats.unchanged, stats.refreshed, stats.removed), (7, 2, 1));
+    let songs = cache.songs();
+    assert_eq!(songs.len(), 28);
+    assert!(songs.iter().any(|s| s.id == "s-new"));
+    assert!(songs.iter().all(|s| s.album_id.as_deref() != Some("al7")));
+    let played = songs.iter().find(|s| s.id == "s5").unwrap();
+    assert_eq!(played.play_count, Some(100));
+}
+
+#[test]
+fn library_cache_picks_up_starred_songs() {
+    let server = MockServer::start(sample_library(30));
+    let client = client(&server);
+    let mut cache = LibraryCache::new();
+    cache.refresh(&client, |_| {}).unwrap();
+
+    // Starring one song leaves the album summary unchanged
+    server.state().songs[4].starred = Some("2024-05-01T10:00:00Z".to_string());
+    let stats = cache.refresh(&client, |_| {}).unwrap();
+    assert_eq!((stats.unchanged, stats.refreshed), (9, 1));
+    let starred = |cache: &LibraryCache| {
+        cache
+            .songs()
+            .into_iter()
+            .find(|s| s.id == "s4")
+            .unwrap()
+            .starred
+    };
+    assert!(starred(&cache).is_some());
+
+    server.state().songs[4].starred = None;
+    let stats = cache.refresh(&client, |_| {}).unwrap();
+    assert_eq!((stats.unchanged, stats.refreshed), (9, 1));
+    assert!(starred(&cache).is_none());
+}
+
+#[test]
+fn library_cache_round_trips_through_the_data_dir() {
+    let server = MockServer::start(sample_library(30));
+    let client = client(&server);
+    let dir = std::env::temp_dir().join(format!("playlist-generator-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(LibraryCache::load(&dir).unwrap().is_none());
+    let mut cache = LibraryCache::new();
+    cache.refresh(&client, |_| {}).unwrap();
+    cache.save(&dir).unwrap();
+
+    let mut loaded = LibraryCache::load(&dir).unwrap().unwrap();
+    assert_eq!(loaded.song_count(), 30);
+    assert_eq!(loaded.synced_at, cache.synced_at);
+    let stats = loaded.refresh(&client, |_| {}).unwrap();
+    assert_eq!((stats.unchanged, stats.refreshed), (10, 0));
+
+    // Files from another version are ignored rather than misread
+    let path = LibraryCache::path(&dir);
+    let content = std::fs::read_to_string(&path).unwrap();
+    std::fs::write(&path, content.replacen("\"version\":1", "\"version\":0", 1)).unwrap();
+    assert!(LibraryCache::load(&dir).unwrap().is_none());
+
+    // A truncated file is discarded instead of failing the run
+    std::fs::write(&path, &content[..content.len() / 2]).unwrap();
+    assert!(LibraryCache::load(&dir).unwrap().is_none());
+    assert!(!path.exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,507 @@
+//! In-process fake OpenSubsonic server for end-to-end tests.
+//!
+//! Keeps songs and playlists in memory, answers the endpoints the client uses and
+//! records every request so tests can assert on the calls that were made.
+
+#![allow(dead_code)] // Each test binary only uses part of the helpers
+
+use playlist_generator::models::Song;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub const USERNAME: &str = "listener";
+pub const PASSWORD: &str = "hunter2";
+pub const API_KEY: &str = "test-api-key";
+
+/// A playlist stored by the fake server
+#[derive(Debug, Clone)]
+pub struct MockPlaylist {
+    pub id: String,
+    pub name: String,
+    pub song_ids: Vec<String>,
+    pub comment: Option<String>,
+    pub public: bool,
+    /// Creation time; later playlists always compare greater
+    pub created: String,
+}
+
+/// One request received by the fake server, with authentication parameters included
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub endpoint: String,
+    pub params: Vec<(String, String)>,
+}
+
+impl RecordedRequest {
+    /// First value of a parameter
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All values of a repeated parameter, in request order
+    pub fn params_named(&self, key: &str) -> Vec<&str> {
+        self.params
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+}
+
+/// Everything the fake server knows. Tests may inspect and modify it between calls.
+#[derive(Debug, Default)]
+pub struct MockState {
+    pub songs: Vec<Song>,
+    pub playlists: Vec<MockPlaylist>,
+    pub requests: Vec<RecordedRequest>,
+    /// OpenSubsonic extensions advertised by getOpenSubsonicExtensions
+    pub extensions: Vec<String>,
+    /// Reject salted token auth with error 41, like an LDAP-backed account
+    pub reject_token_auth: bool,
+    /// Answer the next N requests to an endpoint with HTTP 503, without processing them
+    pub fail_next: HashMap<String, u32>,
+    /// Apply only the removals of the next N updatePlaylist requests, then answer HTTP
+    /// 503, like a server that fails halfway through an edit
+    pub partial_update_next: u32,
+    /// Answer like a plain Subsonic server, without the OpenSubsonic fields in responses
+    pub plain_subsonic: bool,
+    /// Silently drop songs past this length, like a server with a playlist size limit
+    pub max_playlist_len: Option<usize>,
+    next_playlist_id: u32,
+}
+
+impl MockState {
+    /// Add a playlist directly, as if another client had created it
+    pub fn add_playlist(&mut self, name: &str, song_ids: &[&str]) -> String {
+        self.next_playlist_id += 1;
+        let id = format!("pl-{}", self.next_playlist_id);
+        self.playlists.push(MockPlaylist {
+            id: id.clone(),
+            name: name.to_string(),
+            song_ids: song_ids.iter().map(|s| s.to_string()).collect(),
+            comment: None,
+            public: false,
+            created: format!("2024-01-01T00:00:00.{:06}Z", self.next_playlist_id),
+        });
+        id
+    }
+
+    pub fn playlist_named(&self, name: &str) -> Option<&MockPlaylist> {
+        self.playlists.iter().find(|p| p.name == name)
+    }
+
+    /// Requests made to one endpoint, in order
+    pub fn requests_to(&self, endpoint: &str) -> Vec<&RecordedRequest> {
+        self.requests
+            .iter()
+            .filter(|r| r.endpoint == endpoint)
+            .collect()
+    }
+}
+
+/// Handle to a running fake server. The server thread lives until the test process exits.
+pub struct MockServer {
+    pub base_url: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    /// Start a server on a free local port, advertising the formPost extension
+    pub fn start(songs: Vec<Song>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(MockState {
+            songs,
+            extensions: vec!["formPost".to_string()],
+            ..MockState::default()
+        }));
+
+        let server_state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A malformed request only affects that connection
+                let _ = handle_connection(stream, &server_state);
+            }
+        });
+
+        MockServer { base_url, state }
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// A small library spread over a few albums and genres, with BPMs suitable for generation
+pub fn sample_library(count: usize) -> Vec<Song> {
+    const GENRES: [&str; 3] = ["Jazz", "House", "Indie Pop"];
+    (0..count)
+        .map(|i| Song {
+            id: format!("s{i}"),
+            title: format!("Song Number {i}"),
+            artist: format!("Artist {}", i % 17),
+            album: format!("Album {}", i % 10),
+            genre: Some(GENRES[i % GENRES.len()].to_string()),
+            genres: None,
+            bpm: Some(70 + (i as u32 * 7) % 90),
+            duration: Some(180 + (i as u32 * 13) % 120),
+            year: Some(1990 + (i as u32 % 30)),
+            track: Some(1 + i as u32 / 10),
+            play_count: Some(i as u32 % 12),
+            disc_number: Some(1),
+            album_id: Some(format!("al{}", i % 10)),
+            artist_id: Some(format!("ar{}", i % 17)),
+            played: None,
+            starred: None,
+            bit_rate: Some(320),
+            content_type: Some("audio/mpeg".to_string()),
+        })
+        .collect()
+}
+
+fn handle_connection(stream: TcpStream, state: &Mutex<MockState>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let mut params = parse_form(query);
+    if content_length > 0 {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        params.extend(parse_form(&String::from_utf8_lossy(&body)));
+    }
+
+    let request = RecordedRequest {
+        method,
+        endpoint: path.trim_start_matches("/rest/").to_string(),
+        params,
+    };
+    let (status, body) = respond(&mut state.lock().unwrap(), request);
+
+    let body = body.to_string();
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn parse_form(encoded: &str) -> Vec<(String, String)> {
+    encoded
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                urlencoding::decode(&s.replace('+', " "))
+                    .map(|s| s.into_owned())
+                    .unwrap_or_default()
+            };
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+fn ok(mut payload: Value) -> Value {
+    let response = payload.as_object_mut().unwrap();
+    response.insert("status".into(), json!("ok"));
+    response.insert("version".into(), json!("1.16.1"));
+    response.insert("type".into(), json!("mock"));
+    response.insert("serverVersion".into(), json!("0.1"));
+    response.insert("openSubsonic".into(), json!(true));
+    json!({ "subsonic-response": payload })
+}
+
+fn failed(code: u32, message: &str) -> Value {
+    json!({ "subsonic-response": {
+        "status": "failed",
+        "version": "1.16.1",
+        "error": { "code": code, "message": message },
+    }})
+}
+
+fn check_auth(state: &MockState, request: &RecordedRequest) -> Result<(), Value> {
+    if let Some(key) = request.param("apiKey") {
+        if request.param("u").is_some() {
+            return Err(failed(43, "Multiple conflicting authentication mechanisms"));
+        }
+        if !state
+            .extensions
+            .iter()
+            .any(|ext| ext == "apiKeyAuthentication")
+        {
+            return Err(failed(42, "Authentication mechanism not supported"));
+        }
+        return match key == API_KEY {
+            true => Ok(()),
+            false => Err(failed(44, "Invalid API key")),
+        };
+    }
+
+    if request.param("u") != Some(USERNAME) {
+        return Err(failed(40, "Wrong username or password"));
+    }
+    let valid = match (request.param("t"), request.param("s"), request.param("p")) {
+        (Some(_), Some(_), _) if state.reject_token_auth => {
+            return Err(failed(
+                41,
+                "Token authentication not supported for LDAP users",
+            ));
+        }
+        (Some(token), Some(salt), _) => {
+            token == format!("{:x}", md5::compute(format!("{PASSWORD}{salt}")))
+        }
+        (_, _, Some(password)) => match password.strip_prefix("enc:") {
+            Some(hex) => decode_hex(hex).as_deref() == Some(PASSWORD),
+            None => password == PASSWORD,
+        },
+        _ => return Err(failed(10, "Required parameter is missing")),
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(failed(40, "Wrong username or password")),
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    let bytes: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect();
+    String::from_utf8(bytes?).ok()
+}
+
+fn respond(state: &mut MockState, request: RecordedRequest) -> (&'static str, Value) {
+    state.requests.push(request.clone());
+
+    if let Some(remaining) = state.fail_next.get_mut(&request.endpoint)
+        && *remaining > 0
+    {
+        *remaining -= 1;
+        return ("503 Service Unavailable", json!({}));
+    }
+
+    // Extension discovery must work without authentication
+    if request.endpoint == "getOpenSubsonicExtensions" {
+        let extensions: Vec<Value> = state
+            .extensions
+            .iter()
+            .map(|name| json!({ "name": name, "versions": [1] }))
+            .collect();
+        return (
+            "200 OK",
+            ok(json!({ "openSubsonicExtensions": extensions })),
+        );
+    }
+
+    if let Err(error) = check_auth(state, &request) {
+        return ("200 OK", error);
+    }
+
+    let mut body = match request.endpoint.as_str() {
+        "ping" => ok(json!({})),
+        "getRandomSongs" => {
+            let size = request
+                .param("size")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10);
+            let songs: Vec<&Song> = state.songs.iter().take(size).collect();
+            ok(json!({ "randomSongs": { "song": songs } }))
+        }
+        "getAlbumList2" => {
+            // Album ID -> (name, song count, total plays)
+            let mut albums: BTreeMap<String, (String, u32, u32)> = BTreeMap::new();
+            for song in &state.songs {
+                let id = song.album_id.clone().unwrap_or_default();
+                let album = albums.entry(id).or_insert((song.album.clone(), 0, 0));
+                album.1 += 1;
+                album.2 += song.play_count.unwrap_or(0);
+            }
+            let offset: usize = request
+                .param("offset")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+            let size: usize = request
+                .param("size")
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10);
+            let album: Vec<Value> = albums
+                .iter()
+                .skip(offset)
+                .take(size)
+                .map(|(id, (name, count, plays))| {
+                    json!({ "id": id, "name": name, "songCount": count, "playCount": plays })
+                })
+                .collect();
+            ok(json!({ "albumList2": { "album": album } }))
+        }
+        "getStarred2" => {
+            let songs: Vec<&Song> = state.songs.iter().filter(|s| s.starred.is_some()).collect();
+            ok(json!({ "starred2": { "song": songs } }))
+        }
+        "getAlbum" => {
+            let id = request.param("id").unwrap_or_default();
+            let songs: Vec<&Song> = state
+                .songs
+                .iter()
+                .filter(|s| s.album_id.as_deref() == Some(id))
+                .collect();
+            ok(json!({ "album": { "id": id, "song": songs } }))
+        }
+        "getPlaylists" => {
+            let playlist: Vec<Value> = state.playlists.iter().map(playlist_summary).collect();
+            ok(json!({ "playlists": { "playlist": playlist } }))
+        }
+        "getPlaylist" => match find_playlist(state, &request) {
+            Some(playlist) => {
+                let entry: Vec<Value> = playlist
+                    .song_ids
+                    .iter()
+                    .map(|id| match state.songs.iter().find(|s| &s.id == id) {
+                        Some(song) => serde_json::to_value(song).unwrap(),
+                        None => json!({ "id": id, "title": id }),
+                    })
+                    .collect();
+                let mut body = playlist_summary(playlist);
+                body["entry"] = json!(entry);
+                ok(json!({ "playlist": body }))
+            }
+            None => failed(70, "Playlist not found"),
+        },
+        "createPlaylist" => {
+            let Some(name) = request.param("name") else {
+                return ("200 OK", failed(10, "Required parameter is missing: name"));
+            };
+            let song_ids: Vec<&str> = request.params_named("songId");
+            let id = state.add_playlist(name, &song_ids);
+            let max_len = state.max_playlist_len;
+            let playlist = state.playlists.iter_mut().find(|p| p.id == id).unwrap();
+            if let Some(max_len) = max_len {
+                playlist.song_ids.truncate(max_len);
+            }
+            ok(json!({ "playlist": playlist_summary(playlist) }))
+        }
+        "updatePlaylist" => {
+            let Some(index) = state
+                .playlists
+                .iter()
+                .position(|p| Some(p.id.as_str()) == request.param("playlistId"))
+            else {
+                return ("200 OK", failed(70, "Playlist not found"));
+            };
+            let max_len = state.max_playlist_len;
+            let partial = state.partial_update_next > 0;
+            if partial {
+                state.partial_update_next -= 1;
+            }
+            let playlist = &mut state.playlists[index];
+            if let Some(name) = request.param("name") {
+                playlist.name = name.to_string();
+            }
+            if let Some(comment) = request.param("comment") {
+                playlist.comment = Some(comment.to_string());
+            }
+            if let Some(public) = request.param("public") {
+                playlist.public = public == "true";
+            }
+            // Indexes refer to the playlist as it was before this request
+            let removed: Vec<usize> = request
+                .params_named("songIndexToRemove")
+                .iter()
+                .filter_map(|i| i.parse().ok())
+                .collect();
+            playlist.song_ids = playlist
+                .song_ids
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !removed.contains(i))
+                .map(|(_, id)| id.clone())
+                .collect();
+            if partial {
+                return ("503 Service Unavailable", json!({}));
+            }
+            playlist.song_ids = std::mem::take(&mut playlist.song_ids)
+                .into_iter()
+                .chain(
+                    request
+                        .params_named("songIdToAdd")
+                        .into_iter()
+                        .map(String::from),
+                )
+                .collect();
+            if let Some(max_len) = max_len {
+                playlist.song_ids.truncate(max_len);
+            }
+            ok(json!({}))
+        }
+        "deletePlaylist" => match find_playlist(state, &request) {
+            Some(playlist) => {
+                let id = playlist.id.clone();
+                state.playlists.retain(|p| p.id != id);
+                ok(json!({}))
+            }
+            None => failed(70, "Playlist not found"),
+        },
+        _ => failed(0, "Unknown endpoint"),
+    };
+    if state.plain_subsonic
+        && let Some(response) = body["subsonic-response"].as_object_mut()
+    {
+        for field in ["openSubsonic", "type", "serverVersion"] {
+            response.remove(field);
+        }
+    }
+    ("200 OK", body)
+}
+
+fn find_playlist<'a>(state: &'a MockState, request: &RecordedRequest) -> Option<&'a MockPlaylist> {
+    state
+        .playlists
+        .iter()
+        .find(|p| Some(p.id.as_str()) == request.param("id"))
+}
+
+fn playlist_summary(playlist: &MockPlaylist) -> Value {
+    let mut summary = json!({
+        "id": playlist.id,
+        "name": playlist.name,
+        "songCount": playlist.song_ids.len(),
+        "duration": 0,
+        "public": playlist.public,
+        "owner": USERNAME,
+        "created": playlist.created,
+    });
+    if let Some(comment) = &playlist.comment {
+        summary["comment"] = json!(comment);
+    }
+    summary
+}